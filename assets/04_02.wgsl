@@ -3,6 +3,7 @@ struct VertexOutput {
     [[location(0)]] normal: vec3<f32>;
     [[location(1)]] color: vec4<f32>;
     [[location(2)]] tex_coords: vec2<f32>;
+    [[location(3)]] world_position: vec4<f32>;
 };
 
 [[block]]
@@ -20,19 +21,17 @@ var t_diffuse: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_diffuse: sampler;
 
-[[block]]
-struct DirectionLight {
-    direction: vec3<f32>;
-    color: vec3<f32>;
-};
-
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
 [[group(3), binding(0)]]
-var<uniform> directional_light: DirectionLight;
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
 
 [[stage(vertex)]]
 fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>, [[location(2)]] color: vec4<f32>, [[location(3)]] tex_coords: vec2<f32>) -> VertexOutput {
     var out: VertexOutput;
     out.position = global.mat * local.mat * position;
+    out.world_position = global.mat * local.mat * position;
     out.normal = normalize((global.mat * local.mat * vec4<f32>(normal, 0.0)).xyz);
     out.color = color;
     out.tex_coords = tex_coords;
@@ -41,6 +40,22 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let t: f32 = max(0.0, -1.0 * dot(in.normal, directional_light.direction));
-    return vec4<f32>(t * directional_light.color, 1.0);
+    var color: vec3<f32> = lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, in.normal);
+        } else {
+            // The Lambert diffuse term only, without any specular.
+            let direction = incident_direction(light, in.world_position.xyz);
+            let t: f32 = max(0.0, -1.0 * dot(in.normal, direction));
+            color = color + attenuation(light, in.world_position.xyz) * t * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
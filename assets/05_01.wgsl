@@ -21,22 +21,64 @@ var t_diffuse: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_diffuse: sampler;
 
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
+[[group(3), binding(0)]]
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
+
 [[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Directional light
-    direction: vec3<f32>;
-    color: vec3<f32>;
-    // Ambient light
-    ambient: vec3<f32>;
-    // Point light
-    point: vec3<f32>;
-    point_color: vec3<f32>;
-    point_range: f32;
+struct PointShadow {
+    // The near and far planes of the cube face projections.
+    near: f32;
+    far: f32;
+    // Moves the compared point towards the light, in world units.
+    bias: f32;
+    // Non-zero averages several samples around the direction for softer edges.
+    soft: u32;
 };
 
-[[group(3), binding(0)]]
-var<uniform> light: Light;
+[[group(3), binding(1)]]
+var<uniform> point_shadow: PointShadow;
+[[group(3), binding(2)]]
+var t_shadow: texture_depth_cube;
+[[group(3), binding(3)]]
+var s_shadow: sampler_comparison;
+
+// The depth a cube face projection assigns to a point `dist` along its view direction.
+fn cube_face_depth(dist: f32) -> f32 {
+    let n = point_shadow.near;
+    let f = point_shadow.far;
+    return f / (f - n) - f * n / ((f - n) * dist);
+}
+
+// `to_point` goes from the light to the shaded point. The face that is sampled is picked by
+// its largest component, and so is the distance that face stored.
+fn point_shadow_factor(to_point: vec3<f32>) -> f32 {
+    let a = abs(to_point);
+    let major = max(a.x, max(a.y, a.z));
+    let depth = cube_face_depth(major - point_shadow.bias);
+    if (point_shadow.soft == 0u) {
+        return textureSampleCompare(t_shadow, s_shadow, to_point, depth);
+    }
+
+    // Eight samples on the corners of a small cube around the direction plus the center.
+    let radius = major * 0.01;
+    var lit: f32 = textureSampleCompare(t_shadow, s_shadow, to_point, depth);
+    var i: i32 = 0;
+    loop {
+        if (i >= 8) {
+            break;
+        }
+        let corner = vec3<f32>(f32(i & 1), f32((i >> 1u) & 1), f32((i >> 2u) & 1)) * 2.0 - vec3<f32>(1.0, 1.0, 1.0);
+        lit = lit + textureSampleCompare(t_shadow, s_shadow, to_point + corner * radius, depth);
+        i = i + 1;
+    }
+    return lit / 9.0;
+}
+
+// The index of the point light that `t_shadow` was rendered from.
+let SHADOWED_LIGHT: u32 = 1u;
 
 fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
     return max(0.0, -1.0 * dot(normal, light_direction));
@@ -61,14 +103,26 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let directional_diffuse = lambert_diffuse(light.direction, in.normal);
-    let directional_specular = phong_speclar(light.direction, in.normal, in.world_position.xyz, light.eye_position);
-
-    let point_direction = normalize(in.world_position.xyz - light.point);
-    let d = distance(in.world_position.xyz, light.point);
-    let affect = pow(max(0.0, 1.0 - 1.0 / light.point_range * d), 3.0);
-    let point_diffuse = affect * lambert_diffuse(point_direction, in.normal);
-    let point_specular = affect * phong_speclar(point_direction, in.normal, in.world_position.xyz, light.eye_position);
-
-    return vec4<f32>((directional_specular + directional_diffuse) * light.color + (point_diffuse + point_specular) * light.point_color + light.ambient, 1.0);
+    var color: vec3<f32> = lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, in.normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            var lit: f32 = attenuation(light, in.world_position.xyz);
+            if (i == SHADOWED_LIGHT) {
+                lit = lit * point_shadow_factor(in.world_position.xyz - light.position);
+            }
+            let diffuse = lambert_diffuse(direction, in.normal);
+            let specular = phong_speclar(direction, in.normal, in.world_position.xyz, lights.eye_position);
+            color = color + lit * (diffuse + specular) * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
@@ -21,24 +21,74 @@ var t_diffuse: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_diffuse: sampler;
 
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
+[[group(3), binding(0)]]
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
+
 [[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Directional light
-    direction: vec3<f32>;
-    color: vec3<f32>;
-    // Ambient light
-    ambient: vec3<f32>;
-    // Spot light
-    spot_position: vec3<f32>;
-    spot_color: vec3<f32>;
-    spot_direction: vec3<f32>;
-    spot_angle: f32;
-    spot_range: f32;
+struct SpotShadow {
+    light_view_projection: mat4x4<f32>;
+    // Moves the compared point towards the light, in world units.
+    bias: f32;
+    // Non-zero averages 3x3 shadow map texels for softer edges.
+    soft: u32;
+    // The size of one shadow map texel in uv.
+    texel_size: f32;
 };
 
-[[group(3), binding(0)]]
-var<uniform> light: Light;
+[[group(3), binding(1)]]
+var<uniform> spot_shadow: SpotShadow;
+[[group(3), binding(2)]]
+var t_shadow: texture_depth_2d;
+[[group(3), binding(3)]]
+var s_shadow: sampler_comparison;
+
+// The index of the spot light that `t_shadow` was rendered from.
+let SHADOWED_LIGHT: u32 = 1u;
+
+fn spot_shadow_factor(world_position: vec3<f32>, light_position: vec3<f32>) -> f32 {
+    let biased_position = world_position - normalize(world_position - light_position) * spot_shadow.bias;
+    let shadow_position = spot_shadow.light_view_projection * vec4<f32>(biased_position, 1.0);
+    let ndc = shadow_position.xyz / shadow_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    // Outside of the frustum the cone has no light anyway.
+    if (shadow_position.w <= 0.0 || uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        return 0.0;
+    }
+    if (spot_shadow.soft == 0u) {
+        return textureSampleCompare(t_shadow, s_shadow, uv, ndc.z);
+    }
+
+    var lit: f32 = 0.0;
+    var y: i32 = -1;
+    loop {
+        if (y > 1) {
+            break;
+        }
+        var x: i32 = -1;
+        loop {
+            if (x > 1) {
+                break;
+            }
+            let offset = vec2<f32>(f32(x), f32(y)) * spot_shadow.texel_size;
+            lit = lit + textureSampleCompare(t_shadow, s_shadow, uv + offset, ndc.z);
+            x = x + 1;
+        }
+        y = y + 1;
+    }
+    return lit / 9.0;
+}
+
+// The falloff of the book, which narrows the cone with the distance, unlike `attenuation`.
+fn spot_attenuation(light: Light, world_position: vec3<f32>) -> f32 {
+    let d = distance(world_position, light.position);
+    let angle = abs(acos(dot(normalize(world_position - light.position), light.direction)));
+    let affect_range = pow(max(0.0, 1.0 - 1.0 / light.range * d), 3.0);
+    let affect_angle = pow(max(0.0, 1.0 - 1.0 / light.angle * angle * d), 5.0);
+    return affect_range * affect_angle;
+}
 
 fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
     return max(0.0, -1.0 * dot(normal, light_direction));
@@ -63,17 +113,29 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let directional_diffuse = lambert_diffuse(light.direction, in.normal);
-    let directional_specular = phong_speclar(light.direction, in.normal, in.world_position.xyz, light.eye_position);
-
-    let spot_direction = normalize(in.world_position.xyz - light.spot_position);
-    let d = distance(in.world_position.xyz, light.spot_position);
-    let angle = abs(acos(dot(spot_direction, light.spot_direction)));
-    let affect_range = pow(max(0.0, 1.0 - 1.0 / light.spot_range * d), 3.0);
-    let affect_angle = pow(max(0.0, 1.0 - 1.0 / light.spot_angle * angle * d), 5.0);
-
-    let spot_diffuse = affect_range * affect_angle * lambert_diffuse(spot_direction, in.normal);
-    let spot_specular = affect_range * affect_angle * phong_speclar(spot_direction, in.normal, in.world_position.xyz, light.eye_position);
-
-    return vec4<f32>((directional_specular + directional_diffuse) * light.color + (spot_diffuse + spot_specular) * light.spot_color + light.ambient, 1.0);
+    var color: vec3<f32> = lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, in.normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            var lit: f32 = attenuation(light, in.world_position.xyz);
+            if (light.kind == LIGHT_SPOT) {
+                lit = spot_attenuation(light, in.world_position.xyz);
+            }
+            if (i == SHADOWED_LIGHT) {
+                lit = lit * spot_shadow_factor(in.world_position.xyz, light.position);
+            }
+            let diffuse = lambert_diffuse(direction, in.normal);
+            let specular = phong_speclar(direction, in.normal, in.world_position.xyz, lights.eye_position);
+            color = color + lit * (diffuse + specular) * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
@@ -21,17 +21,11 @@ var t_diffuse: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_diffuse: sampler;
 
-[[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Hemisphere light
-    ground_normal: vec3<f32>;
-    ground_color: vec3<f32>;
-    sky_color: vec3<f32>;
-};
-
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
 [[group(3), binding(0)]]
-var<uniform> light: Light;
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
 
 [[stage(vertex)]]
 fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>, [[location(2)]] color: vec4<f32>, [[location(3)]] tex_coords: vec2<f32>) -> VertexOutput {
@@ -46,9 +40,21 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let t = dot(in.normal, light.ground_normal);
-    let t = (t + 1.0) / 2.0;
-    let hemi = mix(light.ground_color, light.sky_color, vec3<f32>(t, t, t));
-
-    return vec4<f32>(hemi, 1.0);
+    var color: vec3<f32> = lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, in.normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            let diffuse = max(0.0, -1.0 * dot(in.normal, direction));
+            color = color + attenuation(light, in.world_position.xyz) * diffuse * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
@@ -23,18 +23,11 @@ var t_specular: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_specular: sampler;
 
-[[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Directional light
-    direction: vec3<f32>;
-    color: vec3<f32>;
-    // Ambient light
-    ambient: vec3<f32>;
-};
-
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
 [[group(3), binding(0)]]
-var<uniform> light: Light;
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
 
 fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
     return max(0.0, -1.0 * dot(normal, light_direction));
@@ -61,11 +54,25 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let color = textureSample(t_specular, s_specular, in.tex_coords);
-
-    let specular = 10.0 * color.a * phong_speclar(light.direction, in.normal, in.world_position.xyz, light.eye_position);
-
-    let diffuse: f32 = lambert_diffuse(light.direction, in.normal);
+    // The specular map is a single channel R8 texture.
+    let specular_mask = textureSample(t_specular, s_specular, in.tex_coords).r;
 
-    return vec4<f32>((specular + diffuse) * light.color + light.ambient, 1.0);
+    var color: vec3<f32> = lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, in.normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            let specular = 10.0 * specular_mask * phong_speclar(direction, in.normal, in.world_position.xyz, lights.eye_position);
+            let diffuse: f32 = lambert_diffuse(direction, in.normal);
+            color = color + attenuation(light, in.world_position.xyz) * (specular + diffuse) * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
@@ -22,19 +22,15 @@ var<uniform> local: Uniforms;
 var t_ambient_occlusion: texture_2d<f32>;
 [[group(2), binding(1)]]
 var s_ambient_occlusion: sampler;
+// Screen space ambient occlusion, one texel per pixel. See `ssao::Ssao`.
+[[group(2), binding(2)]]
+var t_ssao: texture_2d<f32>;
 
-[[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Directional light
-    direction: vec3<f32>;
-    color: vec3<f32>;
-    // Ambient light
-    ambient: vec3<f32>;
-};
-
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
 [[group(3), binding(0)]]
-var<uniform> light: Light;
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
 
 fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
     return max(0.0, -1.0 * dot(normal, light_direction));
@@ -59,13 +55,38 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
     return out;
 }
 
+// The normal pre-pass. `global` has no projection, so these are already view space normals.
 [[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let color = textureSample(t_ambient_occlusion, s_ambient_occlusion, in.tex_coords);
-
-    let specular = phong_speclar(light.direction, in.normal, in.world_position.xyz, light.eye_position);
+fn fs_normal(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(normalize(in.normal), 0.0);
+}
 
-    let diffuse: f32 = lambert_diffuse(light.direction, in.normal);
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // The ambient occlusion map is a single channel R8 texture. Only one of the baked map and
+    // SSAO is bound at a time, the other one is a white texel.
+    let baked = textureSample(t_ambient_occlusion, s_ambient_occlusion, in.tex_coords).r;
+    let size = textureDimensions(t_ssao);
+    let screen = vec2<f32>(in.position.x / f32(size.x), in.position.y / f32(size.y));
+    let ambient_occlusion = baked * textureSample(t_ssao, s_ambient_occlusion, screen).r;
 
-    return vec4<f32>((specular + diffuse) * light.color + color.a * light.ambient, 1.0);
+    // Ambient and hemisphere lights reach every surface, so both are occluded.
+    var color: vec3<f32> = ambient_occlusion * lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + ambient_occlusion * hemisphere_color(light, in.normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            let specular = phong_speclar(direction, in.normal, in.world_position.xyz, lights.eye_position);
+            let diffuse: f32 = lambert_diffuse(direction, in.normal);
+            color = color + attenuation(light, in.world_position.xyz) * (specular + diffuse) * light.color;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
@@ -19,22 +19,60 @@ var<uniform> global: Uniforms;
 var<uniform> local: Uniforms;
 
 [[group(2), binding(0)]]
-var t_ambient_occlusion: texture_2d<f32>;
+var t_orm: texture_2d<f32>;
 [[group(2), binding(1)]]
-var s_ambient_occlusion: sampler;
+var s_orm: sampler;
 
+// The channels of an ORM texture, in the glTF layout.
+struct Orm {
+    occlusion: f32;
+    roughness: f32;
+    metallic: f32;
+};
+
+fn sample_orm(tex_coords: vec2<f32>) -> Orm {
+    let texel = textureSample(t_orm, s_orm, tex_coords);
+    return Orm(texel.r, texel.g, texel.b);
+}
+
+// Metallic-roughness parameters, see `Material` on the Rust side. `f0` is the reflectance of
+// the dielectric part at normal incidence.
 [[block]]
-struct Light {
-    eye_position: vec3<f32>;
-    // Directional light
-    direction: vec3<f32>;
-    color: vec3<f32>;
-    // Ambient light
-    ambient: vec3<f32>;
+struct Material {
+    base_color: vec3<f32>;
+    roughness: f32;
+    f0: vec3<f32>;
+    metallic: f32;
+    distribution: u32;
+    geometry: u32;
+    // Light given off by the surface, scaled by `emissive_strength` and the emissive texture.
+    emissive: vec3<f32>;
+    emissive_strength: f32;
 };
+[[group(2), binding(2)]]
+var<uniform> material: Material;
+// sRGB like a base color texture, sampled with the ORM sampler.
+[[group(2), binding(3)]]
+var t_emissive: texture_2d<f32>;
 
+// `Light`, `Lights` and the helpers come from lights.wgsl, `Fog` and `apply_fog` from fog.wgsl.
 [[group(3), binding(0)]]
-var<uniform> light: Light;
+var<storage> lights: [[access(read)]] Lights;
+[[group(3), binding(5)]]
+var<uniform> fog: Fog;
+
+// Image based lighting, precomputed by `ibl::Environment`.
+[[group(3), binding(1)]]
+var t_irradiance: texture_cube<f32>;
+[[group(3), binding(2)]]
+var t_prefiltered: texture_cube<f32>;
+[[group(3), binding(3)]]
+var t_brdf_lut: texture_2d<f32>;
+[[group(3), binding(4)]]
+var s_environment: sampler;
+
+// Must match `ibl::PREFILTERED_MIP_LEVELS`.
+let PREFILTERED_MIP_LEVELS: f32 = 5.0;
 
 let PI: f32 = 3.14159265359;
 
@@ -42,13 +80,11 @@ fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
     return max(0.0, -1.0 * dot(normal, light_direction)) / PI;
 }
 
-fn fresnel_diffuse(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+fn fresnel_diffuse(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, roughness: f32) -> f32 {
     let to_eye = eye_position - world_position;
 
     let h = normalize(-light_direction + to_eye);
 
-    let roughness = 0.5;
-
     let energy_bias = mix(0.0, 0.5, roughness);
     let energy_factor = mix(1.0, 1.0 / 1.51, roughness);
 
@@ -71,41 +107,87 @@ fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position:
     return pow(max(0.0, dot(ref, to_eye)), 5.0);
 }
 
-fn beckmann(m: f32, t: f32) -> f32 {
-    let t2 = t * t;
-    let t4 = t * t * t * t;
-    let m2 = m * m;
-    let d = 1.0 / (4.0 * m2 * t4);
-    return d * exp((-1.0 / m2) * (1.0 - t2) / t2);
+let DISTRIBUTION_BECKMANN: u32 = 0u;
+let DISTRIBUTION_GGX: u32 = 1u;
+
+let GEOMETRY_COOK_TORRANCE: u32 = 0u;
+let GEOMETRY_SMITH_SCHLICK_GGX: u32 = 1u;
+
+// The perceptual roughness is squared into alpha for both distributions.
+fn beckmann_distribution(nh: f32, roughness: f32) -> f32 {
+    let a = max(roughness * roughness, 0.001);
+    let a2 = a * a;
+    let t2 = max(nh * nh, 0.0001);
+    return exp((t2 - 1.0) / (a2 * t2)) / (PI * a2 * t2 * t2);
 }
 
-fn spc_fresnel(f0: f32, u: f32) -> f32 {
-    return f0 + (1.0 - f0) * pow(1.0 - u, 5.0);
+fn ggx_distribution(nh: f32, roughness: f32) -> f32 {
+    let a = max(roughness * roughness, 0.001);
+    let a2 = a * a;
+    let d = nh * nh * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
 }
 
-fn cook_torrance_specular(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, metalic: f32) -> f32 {
-    let to_eye = eye_position - world_position;
+fn cook_torrance_geometry(nh: f32, nv: f32, nl: f32, vh: f32) -> f32 {
+    return min(1.0, min(2.0 * nh * nv / vh, 2.0 * nh * nl / vh));
+}
+
+fn smith_schlick_ggx_geometry(nv: f32, nl: f32, roughness: f32) -> f32 {
+    // The remapping of k for analytic lights.
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    return (nv / (nv * (1.0 - k) + k)) * (nl / (nl * (1.0 - k) + k));
+}
 
-    let micro_facet: f32 = 0.76;
+fn schlick_fresnel(f0: vec3<f32>, u: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0, 1.0, 1.0) - f0) * pow(1.0 - u, 5.0);
+}
 
-    let f0 = metalic;
-    
+// Metals have no diffuse part and tint their reflection with the base color.
+fn specular_f0(base_color: vec3<f32>, metallic: f32) -> vec3<f32> {
+    return mix(material.f0, base_color, vec3<f32>(metallic, metallic, metallic));
+}
+
+// The microfacet specular term D * G * F / (4 * n.l * n.v), already multiplied by n.l.
+fn microfacet_specular(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, roughness: f32, f0: vec3<f32>) -> vec3<f32> {
+    let to_eye = normalize(eye_position - world_position);
     let h = normalize(-light_direction + to_eye);
 
     let nh = max(0.0, dot(normal, h));
-    let vh = max(0.0, dot(to_eye, h));
+    let vh = max(0.0001, dot(to_eye, h));
     let nl = max(0.0, dot(normal, -light_direction));
-    let nv = max(0.0, dot(normal, to_eye));
+    let nv = max(0.0001, dot(normal, to_eye));
 
-    let d = beckmann(micro_facet, nh);
+    var d: f32 = beckmann_distribution(nh, roughness);
+    if (material.distribution == DISTRIBUTION_GGX) {
+        d = ggx_distribution(nh, roughness);
+    }
 
-    let f = spc_fresnel(f0, vh);
+    var g: f32 = cook_torrance_geometry(nh, nv, nl, vh);
+    if (material.geometry == GEOMETRY_SMITH_SCHLICK_GGX) {
+        g = smith_schlick_ggx_geometry(nv, nl, roughness);
+    }
 
-    let g = min(1.0, min(2.0 * nh * nv / vh, 2.0 * nh * nl / vh));
+    let f = schlick_fresnel(f0, vh);
 
-    let m = PI * nv * nh;
+    return f * (d * g / (4.0 * nv));
+}
 
-    return max(g * d * g / m, 0.0);
+// The split sum approximation of the environment reflected by the surface.
+fn environment_lighting(normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, roughness: f32, f0: vec3<f32>, diffuse_color: vec3<f32>) -> vec3<f32> {
+    let to_eye = normalize(eye_position - world_position);
+    let nv = max(0.0, dot(normal, to_eye));
+    let reflected = reflect(-to_eye, normal);
+
+    let irradiance = textureSample(t_irradiance, s_environment, normal).rgb;
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflected, roughness * (PREFILTERED_MIP_LEVELS - 1.0)).rgb;
+    let brdf = textureSample(t_brdf_lut, s_environment, vec2<f32>(nv, roughness)).rg;
+
+    // Rough surfaces reflect less at grazing angles than the plain Schlick term suggests.
+    let r = vec3<f32>(1.0 - roughness, 1.0 - roughness, 1.0 - roughness);
+    let f = f0 + (max(r, f0) - f0) * pow(1.0 - nv, 5.0);
+    let specular = prefiltered * (f0 * brdf.x + vec3<f32>(brdf.y, brdf.y, brdf.y));
+    let diffuse = irradiance * diffuse_color * (vec3<f32>(1.0, 1.0, 1.0) - f);
+    return diffuse + specular;
 }
 
 [[stage(vertex)]]
@@ -123,8 +205,35 @@ fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let specular = cook_torrance_specular(light.direction, in.normal, in.world_position.xyz, light.eye_position, 0.5);
-    let diffuse = lambert_diffuse(light.direction, in.normal) * fresnel_diffuse(light.direction, in.normal, in.world_position.xyz, light.eye_position);;
-
-    return vec4<f32>((specular + diffuse) * light.color + light.ambient, 1.0);
+    let orm = sample_orm(in.tex_coords);
+    // The texture holds the per texel values, the uniform scales them like glTF factors.
+    let roughness = orm.roughness * material.roughness;
+    let metallic = orm.metallic * material.metallic;
+    let normal = normalize(in.normal);
+
+    let f0 = specular_f0(material.base_color, metallic);
+    let diffuse_color = material.base_color * (1.0 - metallic);
+
+    let ambient = environment_lighting(normal, in.world_position.xyz, lights.eye_position, roughness, f0, diffuse_color);
+    var color: vec3<f32> = orm.occlusion * ambient * lights.ambient;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + orm.occlusion * diffuse_color * hemisphere_color(light, normal);
+        } else {
+            let direction = incident_direction(light, in.world_position.xyz);
+            let specular = microfacet_specular(direction, normal, in.world_position.xyz, lights.eye_position, roughness, f0);
+            let diffuse = diffuse_color * lambert_diffuse(direction, normal) * fresnel_diffuse(direction, normal, in.world_position.xyz, lights.eye_position, roughness);
+            color = color + attenuation(light, in.world_position.xyz) * (specular + diffuse) * light.color;
+        }
+        i = i + 1u;
+    }
+    // Not shadowed or occluded, emission is added after all of the lighting.
+    let emissive = textureSample(t_emissive, s_orm, in.tex_coords).rgb * material.emissive * material.emissive_strength;
+
+    return vec4<f32>(apply_fog(fog, color + emissive, in.world_position.xyz, lights.eye_position, fog.color), 1.0);
 }
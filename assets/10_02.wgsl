@@ -0,0 +1,31 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] tex_coords: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = position;
+    out.tex_coords = tex_coords;
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_hdr: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_hdr: sampler;
+
+[[block]]
+struct Exposure {
+    scale: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> exposure: Exposure;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_hdr, s_hdr, in.tex_coords).rgb * exposure.scale;
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,141 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+[[block]]
+struct Light {
+    eye_position: vec3<f32>;
+    // Directional light
+    direction: vec3<f32>;
+    color: vec3<f32>;
+    // Ambient light
+    ambient: vec3<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> light: Light;
+
+// See `MaterialUniform` on the Rust side.
+[[block]]
+struct Material {
+    // Per channel indices of refraction. They differ for chromatic dispersion.
+    ior: vec3<f32>;
+    surface: u32;
+    // The reflectance of chrome at normal incidence, or the color glass lets through.
+    tint: vec3<f32>;
+};
+[[group(2), binding(1)]]
+var<uniform> material: Material;
+
+// Must match `Surface`.
+let SURFACE_PHONG: u32 = 0u;
+let SURFACE_CHROME: u32 = 1u;
+let SURFACE_GLASS: u32 = 2u;
+
+// `Fog` and `apply_fog` come from fog.wgsl.
+[[group(3), binding(0)]]
+var<uniform> fog: Fog;
+[[group(3), binding(1)]]
+var t_sky: texture_cube<f32>;
+[[group(3), binding(2)]]
+var s_sky: sampler;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+// The light that reaches the eye from the fog, only looking up the sky behind the fragment
+// when the fog takes its color from there. Sampled at an explicit level, as implicit ones are
+// not allowed behind a branch.
+fn fog_color(world_position: vec3<f32>, eye_position: vec3<f32>) -> vec3<f32> {
+    if (fog.sky_scattering == 0u) {
+        return fog.color;
+    }
+    return textureSampleLevel(t_sky, s_sky, normalize(world_position - eye_position), 0.0).rgb;
+}
+
+fn schlick_fresnel(f0: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0, 1.0, 1.0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+fn environment(direction: vec3<f32>) -> vec3<f32> {
+    return textureSample(t_sky, s_sky, direction).rgb;
+}
+
+// A metal reflects the sky, tinted by its Fresnel reflectance.
+fn chrome(incident: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let cos_theta = max(0.0, dot(normal, -incident));
+    return environment(reflect(incident, normal)) * schlick_fresnel(material.tint, cos_theta);
+}
+
+// Snell's law like GLSL's `refract`, which naga does not have yet. Gives the reflection where
+// the light is totally reflected instead.
+fn refract_direction(incident: vec3<f32>, normal: vec3<f32>, eta: f32) -> vec3<f32> {
+    let cos_i = dot(normal, incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if (k > 0.0) {
+        return eta * incident - (eta * cos_i + sqrt(k)) * normal;
+    }
+    return reflect(incident, normal);
+}
+
+// Glass reflects part of the sky and refracts the rest. Only the entry into the glass is
+// traced, which is enough to look like a solid block. Each channel is refracted with its own
+// index, so the colors of the sky separate along edges.
+fn glass(incident: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let refracted = vec3<f32>(
+        environment(refract_direction(incident, normal, 1.0 / material.ior.r)).r,
+        environment(refract_direction(incident, normal, 1.0 / material.ior.g)).g,
+        environment(refract_direction(incident, normal, 1.0 / material.ior.b)).b
+    );
+    let reflected = environment(reflect(incident, normal));
+
+    let r0 = (material.ior.g - 1.0) / (material.ior.g + 1.0);
+    let cos_theta = max(0.0, dot(normal, -incident));
+    let fresnel = schlick_fresnel(vec3<f32>(r0 * r0, r0 * r0, r0 * r0), cos_theta);
+    return mix(refracted * material.tint, reflected, fresnel);
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let specular = phong_speclar(light.direction, normal, in.world_position, light.eye_position);
+    let diffuse = lambert_diffuse(light.direction, normal);
+
+    let incident = normalize(in.world_position - light.eye_position);
+    var color: vec3<f32> = (specular + diffuse) * light.color + light.ambient;
+    if (material.surface == SURFACE_CHROME) {
+        color = chrome(incident, normal);
+    } elseif (material.surface == SURFACE_GLASS) {
+        color = glass(incident, normal);
+    }
+    return vec4<f32>(apply_fog(fog, color, in.world_position, light.eye_position, fog_color(in.world_position, light.eye_position)), 1.0);
+}
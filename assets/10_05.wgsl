@@ -0,0 +1,34 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> global: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+[[group(2), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+[[group(2), binding(1)]]
+var s_diffuse: sampler;
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] color: vec4<f32>, [[location(2)]] tex_coords: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = global.mat * local.mat * position;
+    out.color = color;
+    out.tex_coords = tex_coords;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color + textureSample(t_diffuse, s_diffuse, in.tex_coords);
+}
@@ -0,0 +1,57 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+[[block]]
+struct Light {
+    eye_position: vec3<f32>;
+    // Directional light
+    direction: vec3<f32>;
+    color: vec3<f32>;
+    // Ambient light
+    ambient: vec3<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> light: Light;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let specular = phong_speclar(light.direction, normal, in.world_position, light.eye_position);
+    let diffuse = lambert_diffuse(light.direction, normal);
+
+    return vec4<f32>((specular + diffuse) * light.color + light.ambient, 1.0);
+}
@@ -0,0 +1,64 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+// `Light`, `Lights` and the helpers come from lights.wgsl.
+[[group(2), binding(0)]]
+var<storage> lights: [[access(read)]] Lights;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    var color: vec3<f32> = lights.ambient;
+
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, normal);
+        } else {
+            let direction = incident_direction(light, in.world_position);
+            let diffuse = lambert_diffuse(direction, normal);
+            let specular = phong_speclar(direction, normal, in.world_position, lights.eye_position);
+            color = color + attenuation(light, in.world_position) * (diffuse + specular) * light.color;
+        }
+        i = i + 1u;
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,108 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+[[block]]
+struct Light {
+    eye_position: vec3<f32>;
+    // Directional light
+    direction: vec3<f32>;
+    color: vec3<f32>;
+    // Ambient light
+    ambient: vec3<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> light: Light;
+
+[[block]]
+struct Shadow {
+    light_view_projection: mat4x4<f32>;
+    // Subtracted from the depth of the shaded point before comparing.
+    depth_bias: f32;
+    // Moves the shaded point along its normal, in world units, before projecting it.
+    normal_bias: f32;
+    // The size of one shadow map texel in uv.
+    texel_size: f32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> shadow: Shadow;
+[[group(3), binding(1)]]
+var t_shadow: texture_depth_2d;
+[[group(3), binding(2)]]
+var s_shadow: sampler_comparison;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+// The fraction of the light reaching `world_position`, averaged over 5x5 shadow map texels.
+fn shadow_factor(world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let biased_position = world_position + normal * shadow.normal_bias;
+    let light_position = shadow.light_view_projection * vec4<f32>(biased_position, 1.0);
+    let ndc = light_position.xyz / light_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    // Everything outside the light frustum is lit.
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+    let depth = ndc.z - shadow.depth_bias;
+
+    var lit: f32 = 0.0;
+    var y: i32 = -2;
+    loop {
+        if (y > 2) {
+            break;
+        }
+        var x: i32 = -2;
+        loop {
+            if (x > 2) {
+                break;
+            }
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            lit = lit + textureSampleCompare(t_shadow, s_shadow, uv + offset, depth);
+            x = x + 1;
+        }
+        y = y + 1;
+    }
+    return lit / 25.0;
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let specular = phong_speclar(light.direction, normal, in.world_position, light.eye_position);
+    let diffuse = lambert_diffuse(light.direction, normal);
+    let lit = shadow_factor(in.world_position, normal);
+
+    return vec4<f32>(lit * (specular + diffuse) * light.color + light.ambient, 1.0);
+}
@@ -0,0 +1,165 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+[[block]]
+struct Light {
+    eye_position: vec3<f32>;
+    // Directional light
+    direction: vec3<f32>;
+    color: vec3<f32>;
+    // Ambient light
+    ambient: vec3<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> light: Light;
+
+[[block]]
+struct Cascades {
+    light_view_projection: [[stride(64)]] array<mat4x4<f32>, 4>;
+    // The view depth where each cascade ends.
+    splits: vec4<f32>;
+    // Subtracted from the depth of the shaded point before comparing.
+    depth_bias: vec4<f32>;
+    // Moves the shaded point along its normal, in world units.
+    normal_bias: vec4<f32>;
+    // The camera's view direction, to measure view depth with.
+    camera_forward: vec3<f32>;
+    count: u32;
+    // The fraction at the end of each cascade that fades into the next one.
+    blend: f32;
+    // The size of one shadow map texel in uv.
+    texel_size: f32;
+    // Non-zero tints every pixel by the cascade it reads from.
+    debug: u32;
+};
+
+[[group(3), binding(0)]]
+var<uniform> cascades: Cascades;
+[[group(3), binding(1)]]
+var t_shadow: texture_depth_2d_array;
+[[group(3), binding(2)]]
+var s_shadow: sampler_comparison;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+// The fraction of the light reaching `world_position` according to one cascade, averaged over
+// 3x3 texels.
+fn cascade_shadow_factor(cascade: i32, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let biased_position = world_position + normal * cascades.normal_bias[cascade];
+    let light_position = cascades.light_view_projection[cascade] * vec4<f32>(biased_position, 1.0);
+    let ndc = light_position.xyz / light_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+    let depth = ndc.z - cascades.depth_bias[cascade];
+
+    var lit: f32 = 0.0;
+    var y: i32 = -1;
+    loop {
+        if (y > 1) {
+            break;
+        }
+        var x: i32 = -1;
+        loop {
+            if (x > 1) {
+                break;
+            }
+            let offset = vec2<f32>(f32(x), f32(y)) * cascades.texel_size;
+            lit = lit + textureSampleCompare(t_shadow, s_shadow, uv + offset, cascade, depth);
+            x = x + 1;
+        }
+        y = y + 1;
+    }
+    return lit / 9.0;
+}
+
+// The first cascade that reaches `view_depth`, or the count when it is beyond all of them.
+fn select_cascade(view_depth: f32) -> i32 {
+    var cascade: i32 = 0;
+    loop {
+        if (cascade >= i32(cascades.count) || view_depth < cascades.splits[cascade]) {
+            break;
+        }
+        cascade = cascade + 1;
+    }
+    return cascade;
+}
+
+fn cascade_color(cascade: i32) -> vec3<f32> {
+    if (cascade == 0) {
+        return vec3<f32>(1.0, 0.2, 0.2);
+    } elseif (cascade == 1) {
+        return vec3<f32>(0.2, 1.0, 0.2);
+    } elseif (cascade == 2) {
+        return vec3<f32>(0.2, 0.2, 1.0);
+    } elseif (cascade == 3) {
+        return vec3<f32>(1.0, 1.0, 0.2);
+    }
+    return vec3<f32>(1.0, 1.0, 1.0);
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let specular = phong_speclar(light.direction, normal, in.world_position, light.eye_position);
+    let diffuse = lambert_diffuse(light.direction, normal);
+
+    // Beyond the last cascade there is no shadow. Close to the end of a cascade the next one
+    // fades in, so the change in resolution doesn't show as a seam.
+    let view_depth = dot(in.world_position - light.eye_position, cascades.camera_forward);
+    let cascade = select_cascade(view_depth);
+    var lit: f32 = 1.0;
+    if (cascade < i32(cascades.count)) {
+        lit = cascade_shadow_factor(cascade, in.world_position, normal);
+        var start: f32 = 0.0;
+        if (cascade > 0) {
+            start = cascades.splits[cascade - 1];
+        }
+        let end = cascades.splits[cascade];
+        let fade_start = mix(end, start, cascades.blend);
+        if (view_depth > fade_start && cascade + 1 < i32(cascades.count)) {
+            let fade = (view_depth - fade_start) / (end - fade_start);
+            let next = cascade_shadow_factor(cascade + 1, in.world_position, normal);
+            lit = mix(lit, next, fade);
+        }
+    }
+
+    var color: vec3<f32> = lit * (specular + diffuse) * light.color + light.ambient;
+    if (cascades.debug != 0u) {
+        color = mix(color, cascade_color(cascade), vec3<f32>(0.5, 0.5, 0.5));
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,123 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+// The same data as in `light_clusters.wgsl`, read only.
+[[block]]
+struct Clusters {
+    view: mat4x4<f32>;
+    projection_scale: vec2<f32>;
+    screen_size: vec2<f32>;
+    eye_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    light_count: u32;
+    debug: u32;
+};
+
+struct PointLight {
+    position: vec3<f32>;
+    range: f32;
+    color: vec3<f32>;
+};
+
+[[block]]
+struct PointLights {
+    lights: [[stride(32)]] array<PointLight>;
+};
+
+[[block]]
+struct ClusterLights {
+    indices: [[stride(4)]] array<u32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> clusters: Clusters;
+[[group(2), binding(1)]]
+var<storage> point_lights: [[access(read)]] PointLights;
+[[group(2), binding(2)]]
+var<storage> cluster_lights: [[access(read)]] ClusterLights;
+
+let CLUSTERS_X: u32 = 16u;
+let CLUSTERS_Y: u32 = 9u;
+let CLUSTERS_Z: u32 = 24u;
+let CLUSTER_STRIDE: u32 = 64u;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+// The inverse of the slicing in `light_clusters.wgsl`.
+fn cluster_index(frag_coord: vec2<f32>, world_position: vec3<f32>) -> u32 {
+    let tile = frag_coord / clusters.screen_size;
+    let x = min(u32(tile.x * f32(CLUSTERS_X)), CLUSTERS_X - 1u);
+    let y = min(u32(tile.y * f32(CLUSTERS_Y)), CLUSTERS_Y - 1u);
+    let depth = -(clusters.view * vec4<f32>(world_position, 1.0)).z;
+    let slice = log(depth / clusters.near) / log(clusters.far / clusters.near) * f32(CLUSTERS_Z);
+    let z = min(u32(max(slice, 0.0)), CLUSTERS_Z - 1u);
+    return x + CLUSTERS_X * (y + CLUSTERS_Y * z);
+}
+
+// Black through blue and green to red as the count goes from 0 to 32 and beyond.
+fn heat(count: u32) -> vec3<f32> {
+    let t = min(f32(count) / 32.0, 1.0);
+    return clamp(vec3<f32>(2.0 * t - 1.0, 1.0 - abs(2.0 * t - 1.0), 1.0 - 2.0 * t), vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)) * min(f32(count), 1.0);
+}
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let base = cluster_index(in.position.xy, in.world_position) * CLUSTER_STRIDE;
+    let count = cluster_lights.indices[base];
+
+    if (clusters.debug != 0u) {
+        return vec4<f32>(heat(count), 1.0);
+    }
+
+    var color: vec3<f32> = vec3<f32>(0.02, 0.02, 0.02);
+    var i: u32 = 0u;
+    loop {
+        if (i >= count) {
+            break;
+        }
+        let light_index = cluster_lights.indices[base + 1u + i];
+        let light = point_lights.lights[light_index];
+        let direction = normalize(in.world_position - light.position);
+        let d = distance(in.world_position, light.position);
+        let attenuation = pow(max(0.0, 1.0 - d / light.range), 2.0);
+        let diffuse = lambert_diffuse(direction, normal);
+        let specular = phong_speclar(direction, normal, in.world_position, clusters.eye_position);
+        color = color + attenuation * (diffuse + specular) * light.color;
+        i = i + 1u;
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,71 @@
+// The geometry pass of deferred shading. Nothing is lit here: the surface attributes are
+// written into the G-buffer and `deferred_lighting.wgsl` shades every pixel once afterwards.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] tangent: vec3<f32>;
+    [[location(3)]] bitangent: vec3<f32>;
+    [[location(4)]] tex_coords: vec2<f32>;
+};
+
+// One render target per field, see `gbuffer::GBuffer`.
+struct GBufferOutput {
+    // Base color, alpha unused.
+    [[location(0)]] albedo: vec4<f32>;
+    // World space normal after normal mapping, alpha unused.
+    [[location(1)]] normal: vec4<f32>;
+    // Roughness, metallic and the Phong specular intensity.
+    [[location(2)]] material: vec4<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+// The model matrix and the material of one object.
+[[block]]
+struct Local {
+    model: mat4x4<f32>;
+    base_color: vec4<f32>;
+    roughness: f32;
+    metallic: f32;
+    specular: f32;
+};
+[[group(1), binding(0)]]
+var<uniform> local: Local;
+
+[[group(2), binding(0)]]
+var t_normal: texture_2d<f32>;
+[[group(2), binding(1)]]
+var s_normal: sampler;
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>, [[location(2)]] tangent: vec3<f32>, [[location(3)]] bitangent: vec3<f32>, [[location(4)]] tex_coords: vec2<f32>) -> VertexOutput {
+    let world_position = local.model * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.model * vec4<f32>(normal, 0.0)).xyz);
+    out.tangent = normalize((local.model * vec4<f32>(tangent, 0.0)).xyz);
+    out.bitangent = normalize((local.model * vec4<f32>(bitangent, 0.0)).xyz);
+    out.tex_coords = tex_coords;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> GBufferOutput {
+    let local_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz;
+    let local_normal = normalize(local_normal * 2.0 - vec3<f32>(1.0, 1.0, 1.0));
+    let normal = normalize(in.tangent * local_normal.x + in.bitangent * local_normal.y + in.normal * local_normal.z);
+
+    var out: GBufferOutput;
+    out.albedo = vec4<f32>(local.base_color.rgb, 1.0);
+    out.normal = vec4<f32>(normal, 0.0);
+    out.material = vec4<f32>(local.roughness, local.metallic, local.specular, 0.0);
+    return out;
+}
@@ -0,0 +1,91 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+// See `ToonUniform` on the Rust side.
+[[block]]
+struct Toon {
+    eye_position: vec3<f32>;
+    // The v coordinate of the ramp row in use.
+    ramp: f32;
+    light_direction: vec3<f32>;
+    // The Blinn-Phong term is cut into a hard highlight where it passes this.
+    specular_threshold: f32;
+    base_color: vec3<f32>;
+    shininess: f32;
+    outline_color: vec3<f32>;
+    // In pixels, the same at any distance.
+    outline_width: f32;
+    viewport_size: vec2<f32>;
+};
+[[group(2), binding(0)]]
+var<uniform> toon: Toon;
+// One ramp per row, indexed by the Lambert term along u.
+[[group(2), binding(1)]]
+var t_ramp: texture_2d<f32>;
+[[group(2), binding(2)]]
+var s_ramp: sampler;
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    var out: VertexOutput;
+    out.position = view_projection.mat * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let lambert = max(0.0, -1.0 * dot(normal, toon.light_direction));
+    // The nearest sampler turns the smooth term into the flat bands of the ramp.
+    let shade = textureSample(t_ramp, s_ramp, vec2<f32>(lambert, toon.ramp)).rgb;
+
+    let to_eye = normalize(toon.eye_position - in.world_position);
+    let half_vector = normalize(to_eye - toon.light_direction);
+    var specular: f32 = 0.0;
+    if (lambert > 0.0 && pow(max(0.0, dot(normal, half_vector)), toon.shininess) > toon.specular_threshold) {
+        specular = 1.0;
+    }
+
+    return vec4<f32>(toon.base_color * shade + vec3<f32>(specular, specular, specular), 1.0);
+}
+
+// The inverted hull: the back faces of the mesh pushed out along their normals, so that they
+// show around the silhouette only. The push is done in clip space and scaled by w, which keeps
+// the outline the same number of pixels wide at any distance.
+[[stage(vertex)]]
+fn vs_outline([[location(0)]] position: vec4<f32>, [[location(1)]] normal: vec3<f32>) -> VertexOutput {
+    let world_position = local.mat * position;
+    let world_normal = normalize((local.mat * vec4<f32>(normal, 0.0)).xyz);
+    let clip_position = view_projection.mat * world_position;
+    let clip_normal = (view_projection.mat * vec4<f32>(world_normal, 0.0)).xy;
+    let direction = clip_normal / max(length(clip_normal), 0.0001);
+    // Normalized device coordinates span 2 units across the viewport.
+    let offset = direction * toon.outline_width * 2.0 / toon.viewport_size * clip_position.w;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(clip_position.xy + offset, clip_position.z, clip_position.w);
+    out.world_position = world_position.xyz;
+    out.normal = world_normal;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_outline(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(toon.outline_color, 1.0);
+}
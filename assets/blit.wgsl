@@ -0,0 +1,25 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    out.tex_coords = tc;
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_source: sampler;
+
+// Copies an offscreen target to the whole screen, stretching it if the sizes differ.
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_source, s_source, in.tex_coords);
+}
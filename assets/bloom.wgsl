@@ -0,0 +1,119 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    out.tex_coords = tc;
+    return out;
+}
+
+// The HDR target for the threshold and the composite, one bloom mip level otherwise.
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_source: sampler;
+// The top of the bloom mip chain, read by the composite only.
+[[group(0), binding(2)]]
+var t_bloom: texture_2d<f32>;
+
+[[block]]
+struct Params {
+    // Colors brighter than this bloom.
+    threshold: f32;
+    // The cut is eased in over [threshold - knee, threshold + knee] instead of being a step.
+    knee: f32;
+    // How much of the bloom is added back to the frame.
+    intensity: f32;
+};
+[[group(0), binding(3)]]
+var<uniform> params: Params;
+
+fn splat(v: f32) -> vec3<f32> {
+    return vec3<f32>(v, v, v);
+}
+
+fn source_texel_size() -> vec2<f32> {
+    let size = textureDimensions(t_source);
+    return vec2<f32>(1.0 / f32(size.x), 1.0 / f32(size.y));
+}
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_source, s_source, uv).rgb;
+}
+
+// The dual filter downsample from Marius Bjorge's "Bandwidth-Efficient Rendering". The center
+// and four diagonal bilinear taps each average 2x2 texels, which blurs more than a plain 2x2
+// box at the same cost and does not flicker as bright texels move.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = source_texel_size();
+    var sum: vec3<f32> = sample_source(uv) * 4.0;
+    sum = sum + sample_source(uv + vec2<f32>(-texel.x, -texel.y));
+    sum = sum + sample_source(uv + vec2<f32>(texel.x, -texel.y));
+    sum = sum + sample_source(uv + vec2<f32>(-texel.x, texel.y));
+    sum = sum + sample_source(uv + vec2<f32>(texel.x, texel.y));
+    return sum / 8.0;
+}
+
+// The matching upsample: a tent of eight taps around the texel of the smaller level.
+fn upsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = source_texel_size();
+    var sum: vec3<f32> = sample_source(uv + vec2<f32>(-texel.x * 2.0, 0.0));
+    sum = sum + sample_source(uv + vec2<f32>(texel.x * 2.0, 0.0));
+    sum = sum + sample_source(uv + vec2<f32>(0.0, -texel.y * 2.0));
+    sum = sum + sample_source(uv + vec2<f32>(0.0, texel.y * 2.0));
+    sum = sum + sample_source(uv + vec2<f32>(-texel.x, -texel.y)) * 2.0;
+    sum = sum + sample_source(uv + vec2<f32>(texel.x, -texel.y)) * 2.0;
+    sum = sum + sample_source(uv + vec2<f32>(-texel.x, texel.y)) * 2.0;
+    sum = sum + sample_source(uv + vec2<f32>(texel.x, texel.y)) * 2.0;
+    return sum / 12.0;
+}
+
+// Keeps the part of `color` above the threshold, with a quadratic curve around it.
+fn bright_part(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(params.knee, 0.0001);
+    let curve = clamp(brightness - params.threshold + knee, 0.0, 2.0 * knee);
+    let soft = curve * curve / (4.0 * knee);
+    return color * (max(soft, brightness - params.threshold) / max(brightness, 0.0001));
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform.
+fn aces_filmic(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + splat(b))) / (color * (c * color + splat(d)) + splat(e)), splat(0.0), splat(1.0));
+}
+
+// Renders the HDR target into the first, half size level.
+[[stage(fragment)]]
+fn fs_threshold(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(bright_part(downsample(in.tex_coords)), 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_downsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(downsample(in.tex_coords), 1.0);
+}
+
+// Blended additively into the next larger level, which still holds its downsampled content.
+[[stage(fragment)]]
+fn fs_upsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(upsample(in.tex_coords), 1.0);
+}
+
+// Adds the bloom to the frame and tone maps the sum into the swap chain.
+[[stage(fragment)]]
+fn fs_composite(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = sample_source(in.tex_coords);
+    let bloom = textureSample(t_bloom, s_source, in.tex_coords).rgb;
+    return vec4<f32>(aces_filmic(color + bloom * params.intensity), 1.0);
+}
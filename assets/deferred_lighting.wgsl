@@ -0,0 +1,165 @@
+// The lighting pass of deferred shading. Reads back what `11_05.wgsl` wrote into the G-buffer
+// and evaluates every light once per pixel, no matter how many objects overlap it.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_albedo: texture_2d<f32>;
+[[group(0), binding(1)]]
+var t_normal: texture_2d<f32>;
+[[group(0), binding(2)]]
+var t_material: texture_2d<f32>;
+[[group(0), binding(3)]]
+var t_depth: texture_depth_2d;
+
+[[block]]
+struct Frame {
+    // Takes a pixel from normalized device coordinates back to world space.
+    inverse_view_projection: mat4x4<f32>;
+    debug_view: u32;
+    shading: u32;
+};
+[[group(1), binding(0)]]
+var<uniform> frame: Frame;
+
+// `Light`, `Lights` and the helpers come from lights.wgsl, as in `11_01.wgsl`.
+[[group(2), binding(0)]]
+var<storage> lights: [[access(read)]] Lights;
+
+// Must match `gbuffer::DebugView`.
+let VIEW_LIT: u32 = 0u;
+let VIEW_ALBEDO: u32 = 1u;
+let VIEW_NORMAL: u32 = 2u;
+let VIEW_ROUGHNESS: u32 = 3u;
+let VIEW_METALLIC: u32 = 4u;
+let VIEW_SPECULAR: u32 = 5u;
+let VIEW_DEPTH: u32 = 6u;
+
+let SHADING_PHONG: u32 = 0u;
+let SHADING_COOK_TORRANCE: u32 = 1u;
+
+let PI: f32 = 3.14159265359;
+
+fn lambert_diffuse(light_direction: vec3<f32>, normal: vec3<f32>) -> f32 {
+    return max(0.0, -1.0 * dot(normal, light_direction));
+}
+
+fn phong_speclar(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>) -> f32 {
+    let ref = reflect(light_direction, normal);
+    let to_eye = normalize(eye_position - world_position);
+    return pow(max(0.0, dot(ref, to_eye)), 5.0);
+}
+
+// The perceptual roughness is squared into alpha.
+fn beckmann_distribution(nh: f32, roughness: f32) -> f32 {
+    let a = max(roughness * roughness, 0.001);
+    let a2 = a * a;
+    let t2 = max(nh * nh, 0.0001);
+    return exp((t2 - 1.0) / (a2 * t2)) / (PI * a2 * t2 * t2);
+}
+
+fn cook_torrance_geometry(nh: f32, nv: f32, nl: f32, vh: f32) -> f32 {
+    return min(1.0, min(2.0 * nh * nv / vh, 2.0 * nh * nl / vh));
+}
+
+fn schlick_fresnel(f0: vec3<f32>, u: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0, 1.0, 1.0) - f0) * pow(1.0 - u, 5.0);
+}
+
+// The Cook-Torrance specular term D * G * F / (4 * n.l * n.v), already multiplied by n.l.
+fn cook_torrance_specular(light_direction: vec3<f32>, normal: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, roughness: f32, f0: vec3<f32>) -> vec3<f32> {
+    let to_eye = normalize(eye_position - world_position);
+    let h = normalize(-light_direction + to_eye);
+
+    let nh = max(0.0, dot(normal, h));
+    let vh = max(0.0001, dot(to_eye, h));
+    let nl = max(0.0, dot(normal, -light_direction));
+    let nv = max(0.0001, dot(normal, to_eye));
+
+    let d = beckmann_distribution(nh, roughness);
+    let g = cook_torrance_geometry(nh, nv, nl, vh);
+    let f = schlick_fresnel(f0, vh);
+
+    return f * (d * g / (4.0 * nv));
+}
+
+fn world_position(coords: vec2<i32>, depth: f32) -> vec3<f32> {
+    let size = textureDimensions(t_depth);
+    let uv = vec2<f32>((f32(coords.x) + 0.5) / f32(size.x), (f32(coords.y) + 0.5) / f32(size.y));
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let position = frame.inverse_view_projection * ndc;
+    return position.xyz / position.w;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(i32(in.position.x), i32(in.position.y));
+    let depth = textureLoad(t_depth, coords, 0);
+    // Nothing was drawn here.
+    if (depth >= 1.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let albedo = textureLoad(t_albedo, coords, 0).rgb;
+    let normal = normalize(textureLoad(t_normal, coords, 0).xyz);
+    let material = textureLoad(t_material, coords, 0);
+    let roughness = material.r;
+    let metallic = material.g;
+    let specular_intensity = material.b;
+    let position = world_position(coords, depth);
+
+    if (frame.debug_view == VIEW_ALBEDO) {
+        return vec4<f32>(albedo, 1.0);
+    } elseif (frame.debug_view == VIEW_NORMAL) {
+        return vec4<f32>(normal * 0.5 + vec3<f32>(0.5, 0.5, 0.5), 1.0);
+    } elseif (frame.debug_view == VIEW_ROUGHNESS) {
+        return vec4<f32>(roughness, roughness, roughness, 1.0);
+    } elseif (frame.debug_view == VIEW_METALLIC) {
+        return vec4<f32>(metallic, metallic, metallic, 1.0);
+    } elseif (frame.debug_view == VIEW_SPECULAR) {
+        return vec4<f32>(specular_intensity, specular_intensity, specular_intensity, 1.0);
+    } elseif (frame.debug_view == VIEW_DEPTH) {
+        // The distance to the eye reads better than the non-linear depth buffer value.
+        let d = min(distance(position, lights.eye_position) / 10.0, 1.0);
+        return vec4<f32>(d, d, d, 1.0);
+    }
+
+    let f0 = mix(vec3<f32>(0.04, 0.04, 0.04), albedo, vec3<f32>(metallic, metallic, metallic));
+    let diffuse_color = albedo * (1.0 - metallic);
+
+    var color: vec3<f32> = lights.ambient * albedo;
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.count) {
+            break;
+        }
+        let light = lights.lights[i];
+        if (light.kind == LIGHT_HEMISPHERE) {
+            color = color + hemisphere_color(light, normal) * albedo;
+        } else {
+            let direction = incident_direction(light, position);
+            let diffuse = lambert_diffuse(direction, normal);
+            var lit: vec3<f32> = albedo * diffuse + vec3<f32>(1.0, 1.0, 1.0) * specular_intensity * phong_speclar(direction, normal, position, lights.eye_position);
+            if (frame.shading == SHADING_COOK_TORRANCE) {
+                // The light colors leave out the 1 / PI of the Lambert term, so the
+                // specular term gets it back.
+                lit = diffuse_color * diffuse + cook_torrance_specular(direction, normal, position, lights.eye_position, roughness, f0) * PI;
+            }
+            color = color + attenuation(light, position) * lit * light.color;
+        }
+        i = i + 1u;
+    }
+
+    return vec4<f32>(color, 1.0);
+}
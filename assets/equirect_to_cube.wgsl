@@ -0,0 +1,58 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    out.tex_coords = tc;
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_equirect: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_equirect: sampler;
+
+[[block]]
+struct Face {
+    index: u32;
+};
+[[group(0), binding(2)]]
+var<uniform> face: Face;
+
+let PI: f32 = 3.14159265359;
+
+// The direction through a texel of a cube face, in the usual +X, -X, +Y, -Y, +Z, -Z order.
+fn face_direction(index: u32, tex_coords: vec2<f32>) -> vec3<f32> {
+    let st = tex_coords * 2.0 - vec2<f32>(1.0, 1.0);
+    if (index == 0u) {
+        return vec3<f32>(1.0, -st.y, -st.x);
+    }
+    if (index == 1u) {
+        return vec3<f32>(-1.0, -st.y, st.x);
+    }
+    if (index == 2u) {
+        return vec3<f32>(st.x, 1.0, st.y);
+    }
+    if (index == 3u) {
+        return vec3<f32>(st.x, -1.0, -st.y);
+    }
+    if (index == 4u) {
+        return vec3<f32>(st.x, -st.y, 1.0);
+    }
+    return vec3<f32>(-st.x, -st.y, -1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let d = normalize(face_direction(face.index, in.tex_coords));
+    let uv = vec2<f32>(fract(atan2(d.z, d.x) / (2.0 * PI) + 1.0), acos(d.y) / PI);
+    // The longitude wraps around at u = 0, so pick the level explicitly instead of letting
+    // the derivatives across the seam select a tiny mip.
+    return textureSampleLevel(t_equirect, s_equirect, uv, 0.0);
+}
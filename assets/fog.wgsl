@@ -0,0 +1,58 @@
+// Distance and height fog shared by the 3D samples, see `fog::Fog` on the Rust side. This file
+// is prepended to their shaders, which bind a `Fog` uniform wherever their bind groups have room
+// and pass it to `apply_fog` at the end of `fs_main`.
+
+[[block]]
+struct Fog {
+    color: vec3<f32>;
+    mode: u32;
+    start: f32;
+    end: f32;
+    density: f32;
+    height_density: f32;
+    height_base: f32;
+    height_falloff: f32;
+    sky_scattering: u32;
+};
+
+// Must match `fog::FogMode`.
+let FOG_OFF: u32 = 0u;
+let FOG_LINEAR: u32 = 1u;
+let FOG_EXPONENTIAL: u32 = 2u;
+let FOG_EXPONENTIAL_SQUARED: u32 = 3u;
+
+// The fraction of the color hidden by fog at `view_distance` from the eye.
+fn distance_fog(fog: Fog, view_distance: f32) -> f32 {
+    if (fog.mode == FOG_LINEAR) {
+        return clamp((view_distance - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+    } elseif (fog.mode == FOG_EXPONENTIAL) {
+        return 1.0 - exp(-fog.density * view_distance);
+    } elseif (fog.mode == FOG_EXPONENTIAL_SQUARED) {
+        let d = fog.density * view_distance;
+        return 1.0 - exp(-d * d);
+    }
+    return 0.0;
+}
+
+// The density decays exponentially with height, so its integral along the ray from the eye
+// has a closed form.
+fn height_fog(fog: Fog, eye_position: vec3<f32>, world_position: vec3<f32>) -> f32 {
+    let ray_length = distance(eye_position, world_position);
+    let eye_density = fog.height_density * exp(-fog.height_falloff * (eye_position.y - fog.height_base));
+    let rise = fog.height_falloff * (world_position.y - eye_position.y);
+    var amount: f32 = eye_density * ray_length;
+    // A level ray sees the same density all the way.
+    if (abs(rise) > 0.0001) {
+        amount = amount * (1.0 - exp(-rise)) / rise;
+    }
+    return 1.0 - exp(-amount);
+}
+
+// `fog_color` is the light that reaches the eye from the fog: `fog.color`, or the sky behind the
+// fragment for shaders that have one and `fog.sky_scattering` set. It is up to the caller to
+// only sample the sky in that case.
+fn apply_fog(fog: Fog, color: vec3<f32>, world_position: vec3<f32>, eye_position: vec3<f32>, fog_color: vec3<f32>) -> vec3<f32> {
+    // Both kinds of fog let through their own part of the light.
+    let clear = (1.0 - distance_fog(fog, distance(world_position, eye_position))) * (1.0 - height_fog(fog, eye_position, world_position));
+    return mix(fog_color, color, vec3<f32>(clear, clear, clear));
+}
@@ -0,0 +1,211 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    out.tex_coords = tc;
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_environment: texture_cube<f32>;
+[[group(0), binding(1)]]
+var s_environment: sampler;
+
+[[block]]
+struct Filter {
+    face: u32;
+    roughness: f32;
+    // The width of a face of mip level 0 of `t_environment`.
+    source_size: f32;
+};
+[[group(0), binding(2)]]
+var<uniform> params: Filter;
+
+let PI: f32 = 3.14159265359;
+
+// The direction through a texel of a cube face, in the usual +X, -X, +Y, -Y, +Z, -Z order.
+fn face_direction(index: u32, tex_coords: vec2<f32>) -> vec3<f32> {
+    let st = tex_coords * 2.0 - vec2<f32>(1.0, 1.0);
+    if (index == 0u) {
+        return vec3<f32>(1.0, -st.y, -st.x);
+    }
+    if (index == 1u) {
+        return vec3<f32>(-1.0, -st.y, st.x);
+    }
+    if (index == 2u) {
+        return vec3<f32>(st.x, 1.0, st.y);
+    }
+    if (index == 3u) {
+        return vec3<f32>(st.x, -1.0, -st.y);
+    }
+    if (index == 4u) {
+        return vec3<f32>(st.x, -st.y, 1.0);
+    }
+    return vec3<f32>(-st.x, -st.y, -1.0);
+}
+
+// The i-th of n points of the Hammersley set, for low discrepancy sampling.
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+    var bits: u32 = i;
+    var inverse: f32 = 0.0;
+    var scale: f32 = 0.5;
+    loop {
+        if (bits == 0u) {
+            break;
+        }
+        if ((bits & 1u) == 1u) {
+            inverse = inverse + scale;
+        }
+        bits = bits >> 1u;
+        scale = scale * 0.5;
+    }
+    return vec2<f32>(f32(i) / f32(n), inverse);
+}
+
+// Rotate a direction given around +Z into the space around `normal`.
+fn to_world(v: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var up: vec3<f32> = vec3<f32>(1.0, 0.0, 0.0);
+    if (abs(normal.z) < 0.999) {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return tangent * v.x + bitangent * v.y + normal * v.z;
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+}
+
+fn ggx_distribution(nh: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = nh * nh * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Pick the environment mip whose texels cover about as much solid angle as one sample does.
+// Reading a single texel per sample instead would turn small bright spots into fireflies.
+fn sample_level(pdf: f32, sample_count: u32) -> f32 {
+    let sample_solid_angle = 1.0 / (f32(sample_count) * pdf + 0.0001);
+    let texel_solid_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+    return max(0.0, 0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0);
+}
+
+// Box filters the level above. `t_environment` only views that single level here.
+[[stage(fragment)]]
+fn fs_downsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let d = normalize(face_direction(params.face, in.tex_coords));
+    return textureSampleLevel(t_environment, s_environment, d, 0.0);
+}
+
+// The cosine weighted average of the incoming radiance, which is the irradiance divided by PI.
+// That is exactly what a Lambert surface with an albedo of 1 reflects.
+[[stage(fragment)]]
+fn fs_irradiance(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(face_direction(params.face, in.tex_coords));
+    let sample_count = 1024u;
+
+    var sum: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= sample_count) {
+            break;
+        }
+        let xi = hammersley(i, sample_count);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt(1.0 - xi.y);
+        let sin_theta = sqrt(xi.y);
+        let l = to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+        let level = sample_level(cos_theta / PI, sample_count);
+        sum = sum + textureSampleLevel(t_environment, s_environment, l, level).rgb;
+        i = i + 1u;
+    }
+
+    return vec4<f32>(sum / f32(sample_count), 1.0);
+}
+
+// Prefilter the environment with the GGX lobe of `params.roughness`, assuming the view, normal
+// and reflection directions coincide. This is the first sum of the split sum approximation.
+[[stage(fragment)]]
+fn fs_prefilter(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(face_direction(params.face, in.tex_coords));
+    let sample_count = 512u;
+
+    var sum: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+    var weight: f32 = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= sample_count) {
+            break;
+        }
+        let h = importance_sample_ggx(hammersley(i, sample_count), normal, params.roughness);
+        let l = 2.0 * dot(normal, h) * h - normal;
+        let nl = dot(normal, l);
+        if (nl > 0.0) {
+            // With n = v the half vector pdf D * nh / (4 * vh) reduces to D / 4.
+            let nh = max(0.0, dot(normal, h));
+            let level = sample_level(ggx_distribution(nh, params.roughness) / 4.0, sample_count);
+            sum = sum + textureSampleLevel(t_environment, s_environment, l, level).rgb * nl;
+            weight = weight + nl;
+        }
+        i = i + 1u;
+    }
+
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+fn smith_ggx(nv: f32, nl: f32, roughness: f32) -> f32 {
+    // The remapping of k for image based lighting.
+    let a = roughness * roughness;
+    let k = a / 2.0;
+    let gv = nv / (nv * (1.0 - k) + k);
+    let gl = nl / (nl * (1.0 - k) + k);
+    return gv * gl;
+}
+
+// The second sum of the split sum approximation: the scale and bias to F0 of the specular
+// reflectance, indexed by n.v on x and roughness on y.
+[[stage(fragment)]]
+fn fs_brdf_lut(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let nv = max(in.tex_coords.x, 0.001);
+    let roughness = in.tex_coords.y;
+    let v = vec3<f32>(sqrt(1.0 - nv * nv), 0.0, nv);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+    let sample_count = 1024u;
+
+    var scale: f32 = 0.0;
+    var bias: f32 = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= sample_count) {
+            break;
+        }
+        let h = importance_sample_ggx(hammersley(i, sample_count), normal, roughness);
+        let l = 2.0 * dot(v, h) * h - v;
+        let nl = max(0.0, l.z);
+        let nh = max(0.0, h.z);
+        let vh = max(0.0, dot(v, h));
+        if (nl > 0.0) {
+            let g = smith_ggx(nv, nl, roughness);
+            let g_vis = g * vh / (nh * nv);
+            let fc = pow(1.0 - vh, 5.0);
+            scale = scale + (1.0 - fc) * g_vis;
+            bias = bias + fc * g_vis;
+        }
+        i = i + 1u;
+    }
+
+    return vec4<f32>(scale / f32(sample_count), bias / f32(sample_count), 0.0, 1.0);
+}
@@ -0,0 +1,96 @@
+// Assigns point lights to the clusters of the view frustum: a grid of screen tiles, each cut
+// into depth slices. Every cluster gets the list of lights whose range touches it, so the
+// fragment shader only has to loop over a handful of lights instead of all of them.
+
+[[block]]
+struct Clusters {
+    view: mat4x4<f32>;
+    // projection[0][0] and projection[1][1], to go from NDC back to view space.
+    projection_scale: vec2<f32>;
+    screen_size: vec2<f32>;
+    eye_position: vec3<f32>;
+    near: f32;
+    far: f32;
+    light_count: u32;
+    debug: u32;
+};
+
+struct PointLight {
+    position: vec3<f32>;
+    range: f32;
+    color: vec3<f32>;
+};
+
+[[block]]
+struct PointLights {
+    lights: [[stride(32)]] array<PointLight>;
+};
+
+// CLUSTER_STRIDE entries per cluster: the light count followed by the light indices.
+[[block]]
+struct ClusterLights {
+    indices: [[stride(4)]] array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> clusters: Clusters;
+[[group(0), binding(1)]]
+var<storage> point_lights: [[access(read)]] PointLights;
+[[group(0), binding(2)]]
+var<storage> cluster_lights: [[access(read_write)]] ClusterLights;
+
+// Must match `cluster::CLUSTERS` and `cluster::CLUSTER_STRIDE`.
+let CLUSTERS_X: u32 = 16u;
+let CLUSTERS_Y: u32 = 9u;
+let CLUSTERS_Z: u32 = 24u;
+let CLUSTER_STRIDE: u32 = 64u;
+
+// The view space depth at which slice `z` starts. Slices grow exponentially with the
+// distance, so they look about as thick as they are wide.
+fn slice_depth(z: u32) -> f32 {
+    return clusters.near * pow(clusters.far / clusters.near, f32(z) / f32(CLUSTERS_Z));
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let cluster = id.x;
+    if (cluster >= CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z) {
+        return;
+    }
+    let x = cluster % CLUSTERS_X;
+    let y = (cluster / CLUSTERS_X) % CLUSTERS_Y;
+    let z = cluster / (CLUSTERS_X * CLUSTERS_Y);
+
+    // Tile rows count down from the top of the screen, NDC y points up.
+    let ndc_min = vec2<f32>(f32(x) / f32(CLUSTERS_X) * 2.0 - 1.0, 1.0 - f32(y + 1u) / f32(CLUSTERS_Y) * 2.0);
+    let ndc_max = vec2<f32>(f32(x + 1u) / f32(CLUSTERS_X) * 2.0 - 1.0, 1.0 - f32(y) / f32(CLUSTERS_Y) * 2.0);
+    let near = slice_depth(z);
+    let far = slice_depth(z + 1u);
+
+    // The tile widens with depth, so its bounding box spans both ends of the slice.
+    let near_min = ndc_min * near / clusters.projection_scale;
+    let near_max = ndc_max * near / clusters.projection_scale;
+    let far_min = ndc_min * far / clusters.projection_scale;
+    let far_max = ndc_max * far / clusters.projection_scale;
+    let box_min = vec3<f32>(min(near_min, far_min), -far);
+    let box_max = vec3<f32>(max(near_max, far_max), -near);
+
+    let base = cluster * CLUSTER_STRIDE;
+    var count: u32 = 0u;
+    var i: u32 = 0u;
+    loop {
+        if (i >= clusters.light_count || count >= CLUSTER_STRIDE - 1u) {
+            break;
+        }
+        let light = point_lights.lights[i];
+        let center = (clusters.view * vec4<f32>(light.position, 1.0)).xyz;
+        let closest = clamp(center, box_min, box_max);
+        let offset = center - closest;
+        if (dot(offset, offset) <= light.range * light.range) {
+            cluster_lights.indices[base + 1u + count] = i;
+            count = count + 1u;
+        }
+        i = i + 1u;
+    }
+    cluster_lights.indices[base] = count;
+}
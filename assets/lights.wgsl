@@ -0,0 +1,59 @@
+// The light list shared by the lighting samples, see `light::LightSet` on the Rust side. This
+// file is prepended to their shaders, which bind `Lights` wherever their bind groups have room
+// and loop over it in `fs_main`.
+
+let LIGHT_DIRECTIONAL: u32 = 0u;
+let LIGHT_POINT: u32 = 1u;
+let LIGHT_SPOT: u32 = 2u;
+let LIGHT_HEMISPHERE: u32 = 3u;
+
+// One entry of the light list. Which fields are used depends on the kind:
+// directional lights use `direction`, point lights `position` and `range`, spot lights all
+// of them, and hemisphere lights use `direction` as the ground normal and `ground_color`.
+struct Light {
+    position: vec3<f32>;
+    kind: u32;
+    direction: vec3<f32>;
+    range: f32;
+    color: vec3<f32>;
+    angle: f32;
+    ground_color: vec3<f32>;
+};
+
+[[block]]
+struct Lights {
+    eye_position: vec3<f32>;
+    count: u32;
+    ambient: vec3<f32>;
+    lights: [[stride(64)]] array<Light>;
+};
+
+// How much of a light reaches a point, ignoring the surface orientation.
+fn attenuation(light: Light, world_position: vec3<f32>) -> f32 {
+    if (light.kind == LIGHT_DIRECTIONAL) {
+        return 1.0;
+    }
+    let d = distance(world_position, light.position);
+    let affect_range = pow(max(0.0, 1.0 - d / light.range), 3.0);
+    if (light.kind == LIGHT_POINT) {
+        return affect_range;
+    }
+    let angle = acos(dot(normalize(world_position - light.position), light.direction));
+    let affect_angle = pow(max(0.0, 1.0 - angle / light.angle), 0.5);
+    return affect_range * affect_angle;
+}
+
+// The direction the light travels in when it hits `world_position`.
+fn incident_direction(light: Light, world_position: vec3<f32>) -> vec3<f32> {
+    if (light.kind == LIGHT_DIRECTIONAL) {
+        return light.direction;
+    }
+    return normalize(world_position - light.position);
+}
+
+// Hemisphere lights reach every surface, from the ground color facing down to the sky color
+// facing up.
+fn hemisphere_color(light: Light, normal: vec3<f32>) -> vec3<f32> {
+    let t = (dot(normal, light.direction) + 1.0) / 2.0;
+    return mix(light.ground_color, light.color, vec3<f32>(t, t, t));
+}
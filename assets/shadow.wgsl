@@ -0,0 +1,15 @@
+[[block]]
+struct Uniforms {
+    mat: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> light_view_projection: Uniforms;
+
+[[group(1), binding(0)]]
+var<uniform> local: Uniforms;
+
+// Depth only pass that renders the scene from the light into a shadow map.
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+    return light_view_projection.mat * local.mat * position;
+}
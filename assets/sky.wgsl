@@ -0,0 +1,102 @@
+// The analytic daylight model of Preetham, Shirley and Smits, "A Practical Analytic Model for
+// Daylight". The coefficients only depend on the sun and the turbidity, so `sky::Sky` works them
+// out once and the shader evaluates the Perez function per pixel.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] direction: vec3<f32>;
+};
+
+// See `sky::SkyUniform` on the Rust side.
+[[block]]
+struct Sky {
+    // Takes a point on the far plane in normalized device coordinates to the view direction
+    // through it.
+    ray_matrix: mat4x4<f32>;
+    // The Perez coefficients A to E, each holding the ones of the luminance Y and of the
+    // chromaticity x and y.
+    a: vec3<f32>;
+    // Scales the luminance from kcd/m2 into the range of the exposure curve. Fades out at night.
+    exposure: f32;
+    b: vec3<f32>;
+    c: vec3<f32>;
+    d: vec3<f32>;
+    e: vec3<f32>;
+    // Y, x and y at the zenith, divided by the Perez function there.
+    zenith: vec3<f32>;
+    // Toward the sun, kept just above the horizon where the model still holds.
+    sun_direction: vec3<f32>;
+    sun_color: vec3<f32>;
+    ground_color: vec3<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> sky: Sky;
+
+// In radians, about twice the real one so that the disk is a few pixels across.
+let SUN_ANGULAR_RADIUS: f32 = 0.01;
+
+fn splat(v: f32) -> vec3<f32> {
+    return vec3<f32>(v, v, v);
+}
+
+// Written out, as naga has no `smoothstep` yet. The same as `sky::smoothstep`.
+fn smooth_step(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// The Perez sky luminance distribution for a view direction `cos_theta` from the zenith and
+// `gamma` radians away from the sun.
+fn perez(cos_theta: f32, gamma: f32) -> vec3<f32> {
+    let cos_gamma = cos(gamma);
+    let horizon = splat(1.0) + sky.a * exp(sky.b / max(cos_theta, 0.01));
+    let circumsolar = splat(1.0) + sky.c * exp(sky.d * gamma) + sky.e * cos_gamma * cos_gamma;
+    return horizon * circumsolar;
+}
+
+fn yxy_to_rgb(yxy: vec3<f32>) -> vec3<f32> {
+    let luminance = yxy.x;
+    let x = yxy.y / yxy.z * luminance;
+    let z = (1.0 - yxy.y - yxy.z) / yxy.z * luminance;
+    return vec3<f32>(
+        3.2406 * x - 1.5372 * luminance - 0.4986 * z,
+        -0.9689 * x + 1.8758 * luminance + 0.0415 * z,
+        0.0557 * x - 0.2040 * luminance + 1.0570 * z
+    );
+}
+
+// The same as `Sky::radiance`, which gives the lights their colors.
+fn radiance(direction: vec3<f32>) -> vec3<f32> {
+    let gamma = acos(clamp(dot(direction, sky.sun_direction), -1.0, 1.0));
+    let yxy = sky.zenith * perez(direction.y, gamma);
+    let rgb = yxy_to_rgb(vec3<f32>(yxy.x * sky.exposure, yxy.y, yxy.z));
+    // Must match `sky::NIGHT_SKY`.
+    let night_sky = vec3<f32>(0.002, 0.003, 0.008);
+    return max(splat(1.0) - exp(-1.0 * max(rgb, splat(0.0))), night_sky);
+}
+
+// Draws a full screen quad on the far plane. With a LessEqual depth test it only shows up
+// where nothing else has been drawn.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 1.0, 1.0);
+    out.direction = (sky.ray_matrix * out.position).xyz;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let direction = normalize(in.direction);
+    // Below the horizon the model has nothing to say, so the horizon fades into the ground.
+    let horizon = radiance(normalize(vec3<f32>(direction.x, max(direction.y, 0.001), direction.z)));
+    let below = smooth_step(0.0, 0.05, -direction.y);
+    var color: vec3<f32> = mix(horizon, sky.ground_color, splat(below));
+
+    let sun_angle = acos(clamp(dot(direction, sky.sun_direction), -1.0, 1.0));
+    let disk = 1.0 - smooth_step(SUN_ANGULAR_RADIUS * 0.8, SUN_ANGULAR_RADIUS, sun_angle);
+    color = color + sky.sun_color * disk * (1.0 - below);
+
+    return vec4<f32>(color, 1.0);
+}
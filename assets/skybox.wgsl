@@ -0,0 +1,33 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] direction: vec3<f32>;
+};
+
+[[block]]
+struct Sky {
+    // Inverse of the view projection matrix without the camera translation.
+    inverse_view_projection: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> sky: Sky;
+
+[[group(1), binding(0)]]
+var t_sky: texture_cube<f32>;
+[[group(1), binding(1)]]
+var s_sky: sampler;
+
+// Draws a full screen quad on the far plane. With a LessEqual depth test it only shows up
+// where nothing else has been drawn.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 1.0, 1.0);
+    out.direction = (sky.inverse_view_projection * out.position).xyz;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_sky, s_sky, normalize(in.direction));
+}
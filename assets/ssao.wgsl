@@ -0,0 +1,178 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_depth: texture_depth_2d;
+// View space normals from the normal pre-pass.
+[[group(0), binding(1)]]
+var t_normal: texture_2d<f32>;
+// The occlusion to blur, from the previous pass.
+[[group(0), binding(2)]]
+var t_occlusion: texture_2d<f32>;
+
+[[block]]
+struct Params {
+    // How far around a point occluders are searched for, in view space units.
+    radius: f32;
+    // The visibility is raised to this power.
+    intensity: f32;
+    sample_count: u32;
+};
+[[group(0), binding(3)]]
+var<uniform> params: Params;
+
+let PI: f32 = 3.14159265359;
+// Must not be less than the largest of `ssao::SAMPLE_COUNTS`.
+let MAX_SAMPLES: u32 = 64u;
+let BLUR_RADIUS: i32 = 4;
+// How quickly the blur weight falls off with the depth difference, so edges stay sharp.
+let DEPTH_SHARPNESS: f32 = 100.0;
+
+fn clamp_coords(coords: vec2<i32>) -> vec2<i32> {
+    let size = textureDimensions(t_depth);
+    return clamp(coords, vec2<i32>(0, 0), size - vec2<i32>(1, 1));
+}
+
+// The sample has no projection, so its view space is the clip space of the `global`
+// matrix: x and y in [-1, 1] and the depth buffer value as z, growing away from the eye.
+fn view_position(coords: vec2<i32>) -> vec3<f32> {
+    let size = textureDimensions(t_depth);
+    let uv = vec2<f32>((f32(coords.x) + 0.5) / f32(size.x), (f32(coords.y) + 0.5) / f32(size.y));
+    let depth = textureLoad(t_depth, coords, 0);
+    return vec3<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth);
+}
+
+fn pixel_coords(position: vec3<f32>) -> vec2<i32> {
+    let size = textureDimensions(t_depth);
+    let uv = vec2<f32>(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return clamp_coords(vec2<i32>(i32(uv.x * f32(size.x)), i32(uv.y * f32(size.y))));
+}
+
+// Written out, as naga has no `smoothstep` yet.
+fn smooth_step(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// The i-th of n points of the Hammersley set, for low discrepancy sampling.
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+    var bits: u32 = i;
+    var inverse: f32 = 0.0;
+    var scale: f32 = 0.5;
+    loop {
+        if (bits == 0u) {
+            break;
+        }
+        if ((bits & 1u) == 1u) {
+            inverse = inverse + scale;
+        }
+        bits = bits >> 1u;
+        scale = scale * 0.5;
+    }
+    return vec2<f32>(f32(i) / f32(n), inverse);
+}
+
+// Rotate a direction given around +Z into the space around `normal`.
+fn to_world(v: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var up: vec3<f32> = vec3<f32>(1.0, 0.0, 0.0);
+    if (abs(normal.z) < 0.999) {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return tangent * v.x + bitangent * v.y + normal * v.z;
+}
+
+// Interleaved gradient noise. Every pixel rotates the sample kernel differently, which trades
+// banding for noise that the blur removes.
+fn noise(coords: vec2<i32>) -> f32 {
+    return fract(52.9829189 * fract(0.06711056 * f32(coords.x) + 0.00583715 * f32(coords.y)));
+}
+
+// The fraction of a hemisphere of points around the surface that is hidden behind the depth
+// buffer, turned into visibility.
+[[stage(fragment)]]
+fn fs_ssao(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(i32(in.position.x), i32(in.position.y));
+    let position = view_position(coords);
+    // Nothing was drawn here.
+    if (position.z >= 1.0) {
+        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    }
+    let normal = normalize(textureLoad(t_normal, coords, 0).xyz);
+    let rotation = 2.0 * PI * noise(coords);
+    let sample_count = min(params.sample_count, MAX_SAMPLES);
+
+    var occlusion: f32 = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= sample_count) {
+            break;
+        }
+        let xi = hammersley(i, sample_count);
+        let phi = 2.0 * PI * xi.y + rotation;
+        let cos_theta = sqrt(1.0 - xi.x);
+        let sin_theta = sqrt(xi.x);
+        // More samples close to the point, where occluders matter most.
+        let t = (f32(i) + 0.5) / f32(sample_count);
+        let scale = mix(0.1, 1.0, t * t);
+        let direction = to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+        let sample_position = position + direction * params.radius * scale;
+
+        let scene_depth = textureLoad(t_depth, pixel_coords(sample_position), 0);
+        // Surfaces far in front of the point, like the other side of the screen, don't
+        // occlude it.
+        let range = smooth_step(0.0, 1.0, params.radius / max(abs(position.z - scene_depth), 0.0001));
+        if (scene_depth < sample_position.z - 0.001) {
+            occlusion = occlusion + range;
+        }
+        i = i + 1u;
+    }
+
+    let visibility = pow(1.0 - occlusion / f32(sample_count), params.intensity);
+    return vec4<f32>(visibility, 0.0, 0.0, 1.0);
+}
+
+// A gaussian blur that leaves out texels at a different depth, so occlusion does not bleed
+// across silhouettes.
+fn bilateral_blur(coords: vec2<i32>, direction: vec2<i32>) -> f32 {
+    let center_depth = textureLoad(t_depth, coords, 0);
+    var sum: f32 = 0.0;
+    var weight: f32 = 0.0;
+    var i: i32 = -BLUR_RADIUS;
+    loop {
+        if (i > BLUR_RADIUS) {
+            break;
+        }
+        let sample_coords = clamp_coords(coords + direction * i);
+        let x = f32(i) / f32(BLUR_RADIUS);
+        let depth = textureLoad(t_depth, sample_coords, 0);
+        let w = exp(-2.0 * x * x) * exp(-abs(depth - center_depth) * DEPTH_SHARPNESS);
+        sum = sum + textureLoad(t_occlusion, sample_coords, 0).r * w;
+        weight = weight + w;
+        i = i + 1;
+    }
+    return sum / max(weight, 0.0001);
+}
+
+[[stage(fragment)]]
+fn fs_blur_horizontal(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(i32(in.position.x), i32(in.position.y));
+    return vec4<f32>(bilateral_blur(coords, vec2<i32>(1, 0)), 0.0, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_blur_vertical(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(i32(in.position.x), i32(in.position.y));
+    return vec4<f32>(bilateral_blur(coords, vec2<i32>(0, 1)), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,149 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// Draws a full screen quad as a triangle strip of 4 vertices without any vertex buffer.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let tc = vec2<f32>(f32(vertex_index / 2u), f32(vertex_index % 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(tc.x * 2.0 - 1.0, 1.0 - tc.y * 2.0, 0.0, 1.0);
+    out.tex_coords = tc;
+    return out;
+}
+
+[[group(0), binding(0)]]
+var t_hdr: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_hdr: sampler;
+// Log luminance of the frame, reduced down to 1x1 in the last mip level.
+[[group(0), binding(2)]]
+var t_luminance: texture_2d<f32>;
+// The 1x1 adapted luminance, of the previous frame when adapting and of this frame when
+// tone mapping.
+[[group(0), binding(3)]]
+var t_adapted: texture_2d<f32>;
+
+[[block]]
+struct ToneMapping {
+    exposure: f32;
+    mapping: u32;
+    eye_adaptation: u32;
+    // How far the adapted luminance moves towards the frame average this frame, in [0, 1].
+    adaptation: f32;
+};
+[[group(0), binding(4)]]
+var<uniform> params: ToneMapping;
+
+let OPERATOR_REINHARD: u32 = 0u;
+let OPERATOR_ACES_FILMIC: u32 = 1u;
+let OPERATOR_UNCHARTED2: u32 = 2u;
+let OPERATOR_AGX: u32 = 3u;
+
+// Must match `hdr::LUMINANCE_MIP_LEVELS - 1`.
+let LUMINANCE_LAST_LEVEL: i32 = 8;
+// The scene average is mapped to middle gray.
+let KEY_VALUE: f32 = 0.18;
+
+fn splat(v: f32) -> vec3<f32> {
+    return vec3<f32>(v, v, v);
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(fragment)]]
+fn fs_log_luminance(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_hdr, s_hdr, in.tex_coords).rgb;
+    return vec4<f32>(log(luminance(color) + 0.0001), 0.0, 0.0, 1.0);
+}
+
+// Averaging the logarithm gives the geometric mean, so a few very bright pixels don't make
+// the rest of the frame black.
+[[stage(fragment)]]
+fn fs_adapt(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let average = exp(textureLoad(t_luminance, vec2<i32>(0, 0), LUMINANCE_LAST_LEVEL).r);
+    let previous = textureLoad(t_adapted, vec2<i32>(0, 0), 0).r;
+    let adapted = previous + (average - previous) * params.adaptation;
+    return vec4<f32>(adapted, 0.0, 0.0, 1.0);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (splat(1.0) + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform.
+fn aces_filmic(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + splat(b))) / (color * (c * color + splat(d)) + splat(e)), splat(0.0), splat(1.0));
+}
+
+fn uncharted2_partial(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + splat(c * b)) + splat(d * e)) / (x * (a * x + splat(b)) + splat(d * f))) - splat(e / f);
+}
+
+// John Hable's filmic curve, normalized so that the white point maps to 1.
+fn uncharted2(color: vec3<f32>) -> vec3<f32> {
+    let exposure_bias = 2.0;
+    let white = splat(11.2);
+    return uncharted2_partial(color * exposure_bias) / uncharted2_partial(white);
+}
+
+// A polynomial fit of the AgX default contrast curve, from Benjamin Wrensch's minimal AgX.
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - splat(0.00232);
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104)
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116)
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    let log_color = clamp(log2(max(inset * color, splat(0.0000000001))), splat(min_ev), splat(max_ev));
+    let curve = agx_contrast((log_color - splat(min_ev)) / (max_ev - min_ev));
+    // The curve produces display encoded values, the swap chain expects linear ones.
+    return pow(max(outset * curve, splat(0.0)), splat(2.2));
+}
+
+[[stage(fragment)]]
+fn fs_tonemap(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var exposure: f32 = params.exposure;
+    if (params.eye_adaptation != 0u) {
+        let adapted = textureLoad(t_adapted, vec2<i32>(0, 0), 0).r;
+        exposure = exposure * KEY_VALUE / max(adapted, 0.0001);
+    }
+    let color = textureSample(t_hdr, s_hdr, in.tex_coords).rgb * exposure;
+
+    var mapped: vec3<f32> = reinhard(color);
+    if (params.mapping == OPERATOR_ACES_FILMIC) {
+        mapped = aces_filmic(color);
+    } elseif (params.mapping == OPERATOR_UNCHARTED2) {
+        mapped = uncharted2(color);
+    } elseif (params.mapping == OPERATOR_AGX) {
+        mapped = agx(color);
+    }
+    return vec4<f32>(mapped, 1.0);
+}
@@ -0,0 +1,163 @@
+// Fog over the distance to the eye plus a layer of height fog near the ground, included by the
+// 3D samples with `#[path = "common/fog.rs"] mod fog;`. The shaders get the matching struct and
+// `apply_fog` by prepending `assets/fog.wgsl`, and mix their color towards the fog color, or
+// towards the sky behind the fragment where they have one.
+#![allow(dead_code)]
+
+use bytemuck::{Pod, Zeroable};
+use winit::event::VirtualKeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogMode {
+    Off,
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
+impl FogMode {
+    pub fn next(self) -> Self {
+        match self {
+            FogMode::Off => FogMode::Linear,
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::ExponentialSquared,
+            FogMode::ExponentialSquared => FogMode::Off,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct FogUniform {
+    _color: [f32; 3],
+    _mode: u32,
+    _start: f32,
+    _end: f32,
+    _density: f32,
+    _height_density: f32,
+    _height_base: f32,
+    _height_falloff: f32,
+    _sky_scattering: u32,
+    _pad: u32,
+}
+
+pub struct Fog {
+    pub mode: FogMode,
+    pub height: bool,
+    // Takes the fog color from the sky instead of `color`, so distant objects fade into
+    // the background.
+    pub sky_scattering: bool,
+    pub color: [f32; 3],
+    // Linear fog starts at `start` and hides everything beyond `end`.
+    pub start: f32,
+    pub end: f32,
+    // Of the exponential modes, per unit of distance.
+    pub density: f32,
+    // The height fog density at `height_base`, which falls off by e every
+    // 1 / `height_falloff` units above it.
+    pub height_density: f32,
+    pub height_base: f32,
+    pub height_falloff: f32,
+}
+
+impl Fog {
+    pub fn new() -> Self {
+        Self {
+            mode: FogMode::Exponential,
+            height: true,
+            sky_scattering: true,
+            color: [0.6, 0.65, 0.7],
+            start: 2.0,
+            end: 12.0,
+            density: 0.15,
+            height_density: 0.5,
+            height_base: -0.25,
+            height_falloff: 4.0,
+        }
+    }
+
+    // For the samples without a camera, whose world is clip space and so only about a unit
+    // deep. Starts out off, so they look as before until F or H is pressed.
+    pub fn new_clip_space() -> Self {
+        Self {
+            mode: FogMode::Off,
+            height: false,
+            sky_scattering: false,
+            color: [0.6, 0.65, 0.7],
+            start: 0.3,
+            end: 1.0,
+            density: 1.0,
+            height_density: 2.0,
+            height_base: -0.2,
+            height_falloff: 10.0,
+        }
+    }
+
+    // Thicker fog for a factor above 1: denser exponential fog and a closer linear end.
+    pub fn thicken(&mut self, factor: f32) {
+        self.density *= factor;
+        self.end = self.start + (self.end - self.start) / factor;
+    }
+
+    // The layout entry for a buffer holding `uniform()`, read by fragment shaders.
+    pub fn layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(
+                    std::mem::size_of::<FogUniform>() as wgpu::BufferAddress
+                ),
+            },
+            count: None,
+        }
+    }
+
+    // F cycles through the distance fog modes, H toggles height fog and Minus and Equals thin
+    // and thicken the fog. Returns whether the key was one of them.
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode) -> bool {
+        match keycode {
+            VirtualKeyCode::F => self.mode = self.mode.next(),
+            VirtualKeyCode::H => self.height = !self.height,
+            VirtualKeyCode::Equals => self.thicken(1.25),
+            VirtualKeyCode::Minus => self.thicken(1.0 / 1.25),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn uniform(&self) -> FogUniform {
+        FogUniform {
+            _color: self.color,
+            _mode: self.mode as u32,
+            _start: self.start,
+            _end: self.end,
+            _density: self.density,
+            _height_density: if self.height {
+                self.height_density
+            } else {
+                0.0
+            },
+            _height_base: self.height_base,
+            _height_falloff: self.height_falloff,
+            _sky_scattering: self.sky_scattering as u32,
+            _pad: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let distance = match self.mode {
+            FogMode::Off => "no distance fog".to_string(),
+            FogMode::Linear => format!("linear fog {:.1}..{:.1}", self.start, self.end),
+            mode => format!("{:?} fog density {:.3}", mode, self.density),
+        };
+        format!(
+            "{}, height fog {}, sky in-scattering {}",
+            distance,
+            if self.height { "on" } else { "off" },
+            if self.sky_scattering { "on" } else { "off" }
+        )
+    }
+}
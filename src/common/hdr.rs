@@ -0,0 +1,498 @@
+// The HDR target, tone mapping and eye adaptation of the lighting samples, included by each of
+// them with `#[path = "common/hdr.rs"] mod hdr;`. The scene renders into a half float target so
+// bright lights keep their range. A final pass scales it by the exposure and maps it to the swap
+// chain with a tone mapping curve. With eye adaptation the exposure also follows the average
+// luminance of recent frames.
+#![allow(dead_code)]
+
+use std::{borrow::Cow, num::NonZeroU32, time::Instant};
+
+use bytemuck::{Pod, Zeroable};
+use winit::event::VirtualKeyCode;
+
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const LUMINANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+// The frame is reduced at a fixed resolution, so the last level is exactly 1x1.
+const LUMINANCE_SIZE: u32 = 256;
+// `LUMINANCE_LAST_LEVEL` in the shader is one less.
+const LUMINANCE_MIP_LEVELS: u32 = 9;
+// How quickly the eye follows a change in brightness, per second.
+const ADAPTATION_SPEED: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    Reinhard,
+    AcesFilmic,
+    Uncharted2,
+    Agx,
+}
+
+impl ToneMapping {
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapping::Reinhard => ToneMapping::AcesFilmic,
+            ToneMapping::AcesFilmic => ToneMapping::Uncharted2,
+            ToneMapping::Uncharted2 => ToneMapping::Agx,
+            ToneMapping::Agx => ToneMapping::Reinhard,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Params {
+    _exposure: f32,
+    _mapping: u32,
+    _eye_adaptation: u32,
+    _adaptation: f32,
+}
+
+pub struct HdrPipeline {
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    pub eye_adaptation: bool,
+    _target: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    params_buffer: wgpu::Buffer,
+    luminance_views: Vec<wgpu::TextureView>,
+    // Ping-ponged 1x1 textures: each frame adapts from one into the other.
+    adapted_views: [wgpu::TextureView; 2],
+    current: usize,
+    first_frame: bool,
+    last_frame: Instant,
+    log_luminance_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    adapt_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    log_luminance_bind_group: wgpu::BindGroup,
+    downsample_bind_groups: Vec<wgpu::BindGroup>,
+    adapt_bind_groups: Vec<wgpu::BindGroup>,
+    tonemap_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl HdrPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sc_desc: &wgpu::SwapChainDescriptor,
+    ) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("tonemap"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/tonemap.wgsl"
+            ))),
+            flags: wgpu::ShaderFlags::all(),
+        });
+        let mipmap_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("mipmap"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/mipmap.wgsl"
+            ))),
+            flags: wgpu::ShaderFlags::all(),
+        });
+
+        let log_luminance_pipeline =
+            create_pipeline(device, &shader, "fs_log_luminance", LUMINANCE_FORMAT);
+        let downsample_pipeline =
+            create_pipeline(device, &mipmap_shader, "fs_main", LUMINANCE_FORMAT);
+        let adapt_pipeline = create_pipeline(device, &shader, "fs_adapt", LUMINANCE_FORMAT);
+        let tonemap_pipeline = create_pipeline(device, &shader, "fs_tonemap", sc_desc.format);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("hdr"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tone mapping"),
+            size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let luminance = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("log luminance"),
+            size: wgpu::Extent3d {
+                width: LUMINANCE_SIZE,
+                height: LUMINANCE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: LUMINANCE_MIP_LEVELS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: LUMINANCE_FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+        let luminance_view = luminance.create_view(&wgpu::TextureViewDescriptor::default());
+        let luminance_views = (0..LUMINANCE_MIP_LEVELS)
+            .map(|mip| {
+                luminance.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("log luminance mip"),
+                    base_mip_level: mip,
+                    mip_level_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let downsample_layout = downsample_pipeline.get_bind_group_layout(0);
+        let downsample_bind_groups = luminance_views[..luminance_views.len() - 1]
+            .iter()
+            .map(|source| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &downsample_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: Some("log luminance downsample"),
+                })
+            })
+            .collect();
+
+        let adapted_view = || {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("adapted luminance"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: LUMINANCE_FORMAT,
+                    usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let adapted_views = [adapted_view(), adapted_view()];
+        // Tone mapping reads the adapted luminance even before eye adaptation first runs, so
+        // start both out cleared instead of undefined.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("clear adapted luminance"),
+        });
+        for view in adapted_views.iter() {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("clear adapted luminance"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+        }
+        queue.submit(Some(encoder.finish()));
+
+        // Adapting from view `i` renders into the other one.
+        let adapt_layout = adapt_pipeline.get_bind_group_layout(0);
+        let adapt_bind_groups = adapted_views
+            .iter()
+            .map(|previous| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &adapt_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&luminance_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(previous),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("adapt"),
+                })
+            })
+            .collect();
+
+        let (target, view) = create_target(device, sc_desc);
+        let (log_luminance_bind_group, tonemap_bind_groups) = create_target_bind_groups(
+            device,
+            &log_luminance_pipeline,
+            &tonemap_pipeline,
+            &view,
+            &sampler,
+            &adapted_views,
+            &params_buffer,
+        );
+
+        Self {
+            exposure: 1.0,
+            tone_mapping: ToneMapping::AcesFilmic,
+            eye_adaptation: true,
+            _target: target,
+            view,
+            sampler,
+            params_buffer,
+            luminance_views,
+            adapted_views,
+            current: 0,
+            first_frame: true,
+            last_frame: Instant::now(),
+            log_luminance_pipeline,
+            downsample_pipeline,
+            adapt_pipeline,
+            tonemap_pipeline,
+            log_luminance_bind_group,
+            downsample_bind_groups,
+            adapt_bind_groups,
+            tonemap_bind_groups,
+        }
+    }
+
+    // The view the scene renders into.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        let (target, view) = create_target(device, sc_desc);
+        let (log_luminance_bind_group, tonemap_bind_groups) = create_target_bind_groups(
+            device,
+            &self.log_luminance_pipeline,
+            &self.tonemap_pipeline,
+            &view,
+            &self.sampler,
+            &self.adapted_views,
+            &self.params_buffer,
+        );
+        self._target = target;
+        self.view = view;
+        self.log_luminance_bind_group = log_luminance_bind_group;
+        self.tonemap_bind_groups = tonemap_bind_groups;
+    }
+
+    // T cycles the tone mapping operator, PageUp and PageDown change the exposure by half a
+    // stop and A toggles eye adaptation. Returns whether the key was one of them.
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode) -> bool {
+        match keycode {
+            VirtualKeyCode::T => self.tone_mapping = self.tone_mapping.next(),
+            VirtualKeyCode::PageUp => self.exposure *= 2f32.sqrt(),
+            VirtualKeyCode::PageDown => self.exposure /= 2f32.sqrt(),
+            VirtualKeyCode::A => self.eye_adaptation = !self.eye_adaptation,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "{:?}, exposure {:+.1} EV, eye adaptation {}",
+            self.tone_mapping,
+            self.exposure.log2(),
+            if self.eye_adaptation { "on" } else { "off" }
+        )
+    }
+
+    // Tone map the HDR target into `output`.
+    pub fn render(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+    ) {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        // Start from the first frame's average instead of fading in from black.
+        let adaptation = if self.first_frame {
+            1.0
+        } else {
+            1.0 - (-dt * ADAPTATION_SPEED).exp()
+        };
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&Params {
+                _exposure: self.exposure,
+                _mapping: self.tone_mapping as u32,
+                _eye_adaptation: self.eye_adaptation as u32,
+                _adaptation: adaptation,
+            }),
+        );
+
+        if self.eye_adaptation {
+            fullscreen_pass(
+                encoder,
+                &self.luminance_views[0],
+                &self.log_luminance_pipeline,
+                &self.log_luminance_bind_group,
+            );
+            for (target, bind_group) in self.luminance_views[1..]
+                .iter()
+                .zip(self.downsample_bind_groups.iter())
+            {
+                fullscreen_pass(encoder, target, &self.downsample_pipeline, bind_group);
+            }
+            let next = 1 - self.current;
+            fullscreen_pass(
+                encoder,
+                &self.adapted_views[next],
+                &self.adapt_pipeline,
+                &self.adapt_bind_groups[self.current],
+            );
+            self.current = next;
+            self.first_frame = false;
+        }
+
+        fullscreen_pass(
+            encoder,
+            output,
+            &self.tonemap_pipeline,
+            &self.tonemap_bind_groups[self.current],
+        );
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+    })
+}
+
+fn create_target(
+    device: &wgpu::Device,
+    sc_desc: &wgpu::SwapChainDescriptor,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("hdr target"),
+        size: wgpu::Extent3d {
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    (target, view)
+}
+
+// The bind groups that read the HDR target, which change with the window size. Tone
+// mapping reads whichever adapted luminance was written last.
+fn create_target_bind_groups(
+    device: &wgpu::Device,
+    log_luminance_pipeline: &wgpu::RenderPipeline,
+    tonemap_pipeline: &wgpu::RenderPipeline,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    adapted_views: &[wgpu::TextureView; 2],
+    params_buffer: &wgpu::Buffer,
+) -> (wgpu::BindGroup, Vec<wgpu::BindGroup>) {
+    let log_luminance_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &log_luminance_pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("log luminance"),
+    });
+
+    let tonemap_layout = tonemap_pipeline.get_bind_group_layout(0);
+    let tonemap_bind_groups = adapted_views
+        .iter()
+        .map(|adapted| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &tonemap_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(adapted),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("tonemap"),
+            })
+        })
+        .collect();
+
+    (log_luminance_bind_group, tonemap_bind_groups)
+}
+
+fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, bind_group, &[]);
+    rpass.draw(0..4, 0..1);
+}
@@ -0,0 +1,223 @@
+// The light list of the lighting samples, included by each of them with
+// `#[path = "common/light.rs"] mod light;`. The shaders get the matching structs and helpers by
+// prepending `assets/lights.wgsl`. Not every sample uses every light kind or method.
+#![allow(dead_code)]
+
+use bytemuck::{Pod, Zeroable};
+use std::mem::size_of;
+
+const DIRECTIONAL: u32 = 0;
+const POINT: u32 = 1;
+const SPOT: u32 = 2;
+const HEMISPHERE: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+    },
+    // The light fades out completely at `range`.
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        range: f32,
+    },
+    // `angle` is the half angle of the cone in radians.
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        angle: f32,
+        range: f32,
+    },
+    // Blends between the two colors depending on how much a surface faces `up`.
+    Hemisphere {
+        sky_color: [f32; 3],
+        ground_color: [f32; 3],
+        up: [f32; 3],
+    },
+}
+
+// Matches `Light` in the shader, 64 bytes per entry.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+struct GpuLight {
+    _position: [f32; 3],
+    _kind: u32,
+    _direction: [f32; 3],
+    _range: f32,
+    _color: [f32; 3],
+    _angle: f32,
+    _ground_color: [f32; 3],
+    _pad: f32,
+}
+
+// Matches the fields of `Lights` in front of the array.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Header {
+    _eye_position: [f32; 3],
+    _count: u32,
+    _ambient: [f32; 3],
+    _pad: f32,
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    cgmath::InnerSpace::normalize(cgmath::Vector3::from(v)).into()
+}
+
+impl Light {
+    fn to_gpu(self) -> GpuLight {
+        match self {
+            Light::Directional { direction, color } => GpuLight {
+                _kind: DIRECTIONAL,
+                _direction: normalize(direction),
+                _color: color,
+                ..Default::default()
+            },
+            Light::Point {
+                position,
+                color,
+                range,
+            } => GpuLight {
+                _kind: POINT,
+                _position: position,
+                _color: color,
+                _range: range,
+                ..Default::default()
+            },
+            Light::Spot {
+                position,
+                direction,
+                color,
+                angle,
+                range,
+            } => GpuLight {
+                _kind: SPOT,
+                _position: position,
+                _direction: normalize(direction),
+                _color: color,
+                _angle: angle,
+                _range: range,
+                ..Default::default()
+            },
+            Light::Hemisphere {
+                sky_color,
+                ground_color,
+                up,
+            } => GpuLight {
+                _kind: HEMISPHERE,
+                _direction: normalize(up),
+                _color: sky_color,
+                _ground_color: ground_color,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// A list of lights of any length, stored in a read-only storage buffer that shaders loop over.
+// Changes are uploaded by `update`, which grows the buffer when it runs out of room. The samples
+// put the buffer into their own bind groups next to whatever else their lights need, such as
+// shadow maps.
+pub struct LightSet {
+    eye_position: [f32; 3],
+    ambient: [f32; 3],
+    lights: Vec<Light>,
+    capacity: usize,
+    buffer: wgpu::Buffer,
+}
+
+impl LightSet {
+    const INITIAL_CAPACITY: usize = 16;
+
+    pub fn new(device: &wgpu::Device, eye_position: [f32; 3], ambient: [f32; 3]) -> Self {
+        Self {
+            eye_position,
+            ambient,
+            lights: Vec::new(),
+            capacity: Self::INITIAL_CAPACITY,
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light set"),
+            size: (size_of::<Header>() + capacity * size_of::<GpuLight>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // The layout entry for `binding()`, read by fragment shaders.
+    pub fn layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn push(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn pop(&mut self) -> Option<Light> {
+        self.lights.pop()
+    }
+
+    pub fn get(&self, index: usize) -> Light {
+        self.lights[index]
+    }
+
+    // Replaces the light at `index`, for lights that follow something else such as the sky.
+    pub fn set(&mut self, index: usize, light: Light) {
+        self.lights[index] = light;
+    }
+
+    // Uploads the lights. Returns whether the buffer had to grow, in which case bind groups
+    // using `binding()` have to be recreated.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let grown = self.lights.len() > self.capacity;
+        if grown {
+            self.capacity = self.lights.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+
+        let header = Header {
+            _eye_position: self.eye_position,
+            _count: self.lights.len() as u32,
+            _ambient: self.ambient,
+            _pad: 0.0,
+        };
+        let lights = self
+            .lights
+            .iter()
+            .map(|light| light.to_gpu())
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&header));
+        if !lights.is_empty() {
+            queue.write_buffer(
+                &self.buffer,
+                size_of::<Header>() as wgpu::BufferAddress,
+                bytemuck::cast_slice(&lights),
+            );
+        }
+        grown
+    }
+}
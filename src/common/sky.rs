@@ -0,0 +1,381 @@
+// The Preetham, Shirley and Smits daylight model, see `sky.wgsl`, included by the lighting
+// samples with `#[path = "common/sky.rs"] mod sky;` next to `light`. The time of day places the
+// sun, and the same model that draws the background of sample_05_04 then gives the direction
+// and color of the directional light and the sky and ground colors of the hemisphere light, so
+// the scene is always lit by the sky behind it.
+#![allow(dead_code)]
+
+use super::light::{Light, LightSet};
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Matrix4, Vector3, Vector4};
+use std::f32::consts::PI;
+use winit::event::VirtualKeyCode;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct SkyUniform {
+    _ray_matrix: [[f32; 4]; 4],
+    _a: [f32; 3],
+    _exposure: f32,
+    _b: [f32; 3],
+    _pad0: f32,
+    _c: [f32; 3],
+    _pad1: f32,
+    _d: [f32; 3],
+    _pad2: f32,
+    _e: [f32; 3],
+    _pad3: f32,
+    _zenith: [f32; 3],
+    _pad4: f32,
+    _sun_direction: [f32; 3],
+    _pad5: f32,
+    _sun_color: [f32; 3],
+    _pad6: f32,
+    _ground_color: [f32; 3],
+    _pad7: f32,
+}
+
+// In hours. The sun is above the horizon in between.
+const SUNRISE: f32 = 6.0;
+const SUNSET: f32 = 18.0;
+// How high the sun gets at noon, and how far to either side of the view it rises and sets.
+const NOON_ELEVATION: f32 = 40.0;
+const SUNRISE_AZIMUTH: f32 = 70.0;
+// Scales the luminance from kcd/m2 into the range of the exposure curve.
+const EXPOSURE: f32 = 0.08;
+// The sky never goes fully black. Must match `sky.wgsl`.
+const NIGHT_SKY: [f32; 3] = [0.002, 0.003, 0.008];
+const GROUND_ALBEDO: f32 = 0.3;
+// The sample has no camera, so the background is seen through a fixed one at the origin,
+// looking down +z like the teapot and tilted up to show more sky than ground.
+const FIELD_OF_VIEW: f32 = 70.0;
+const PITCH: f32 = 15.0;
+
+pub struct Sky {
+    // In hours, wrapping around at 24.
+    pub time_of_day: f32,
+    // How hazy the air is, from 2 for a clear sky to 10 for a thick haze.
+    pub turbidity: f32,
+}
+
+impl Sky {
+    const MIN_TURBIDITY: f32 = 2.0;
+    const MAX_TURBIDITY: f32 = 10.0;
+
+    pub fn new() -> Self {
+        Self {
+            time_of_day: 15.0,
+            turbidity: 3.0,
+        }
+    }
+
+    pub fn advance(&mut self, hours: f32) {
+        self.time_of_day = (self.time_of_day + hours).rem_euclid(24.0);
+    }
+
+    pub fn thicken(&mut self, amount: f32) {
+        self.turbidity = (self.turbidity + amount).clamp(Self::MIN_TURBIDITY, Self::MAX_TURBIDITY);
+    }
+
+    // Points toward the sun. It rises on the right, passes in front of the view at noon
+    // and sets on the left, and goes as far below the horizon at midnight.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let day = (self.time_of_day - SUNRISE) / (SUNSET - SUNRISE);
+        let elevation = NOON_ELEVATION.to_radians() * (day * PI).sin();
+        let azimuth = SUNRISE_AZIMUTH.to_radians() * (1.0 - 2.0 * day);
+        Vector3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        )
+    }
+
+    // The model breaks down once the sun sets, so it is evaluated with the sun held just
+    // above the horizon and faded out by `daylight` instead.
+    fn model_sun_direction(&self) -> Vector3<f32> {
+        let mut sun = self.sun_direction();
+        sun.y = sun.y.max(0.02);
+        sun.normalize()
+    }
+
+    fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.05, self.sun_direction().y)
+    }
+
+    // The Perez coefficients A to E of the luminance Y and the chromaticity x and y.
+    fn coefficients(&self) -> [[f32; 3]; 5] {
+        let t = self.turbidity;
+        [
+            [
+                0.1787 * t - 1.4630,
+                -0.0193 * t - 0.2592,
+                -0.0167 * t - 0.2608,
+            ],
+            [
+                -0.3554 * t + 0.4275,
+                -0.0665 * t + 0.0008,
+                -0.0950 * t + 0.0092,
+            ],
+            [
+                -0.0227 * t + 5.3251,
+                -0.0004 * t + 0.2125,
+                -0.0079 * t + 0.2102,
+            ],
+            [
+                0.1206 * t - 2.5771,
+                -0.0641 * t - 0.8989,
+                -0.0441 * t - 1.6537,
+            ],
+            [
+                -0.0670 * t + 0.3703,
+                -0.0033 * t + 0.0452,
+                -0.0109 * t + 0.0529,
+            ],
+        ]
+    }
+
+    // Y, x and y at the zenith, divided by the Perez function there. Multiplying by the
+    // Perez function of any other direction then gives the sky in that direction.
+    fn zenith(&self) -> [f32; 3] {
+        let t = self.turbidity;
+        let theta = self.model_sun_direction().y.acos();
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let powers = Vector4::new(theta.powi(3), theta.powi(2), theta, 1.0);
+        let chromaticity = |t2: Vector4<f32>, t1: Vector4<f32>, t0: Vector4<f32>| {
+            t * t * t2.dot(powers) + t * t1.dot(powers) + t0.dot(powers)
+        };
+        let x = chromaticity(
+            Vector4::new(0.00166, -0.00375, 0.00209, 0.0),
+            Vector4::new(-0.02903, 0.06377, -0.03202, 0.00394),
+            Vector4::new(0.11693, -0.21196, 0.06052, 0.25886),
+        );
+        let y = chromaticity(
+            Vector4::new(0.00275, -0.00610, 0.00317, 0.0),
+            Vector4::new(-0.04214, 0.08970, -0.04153, 0.00516),
+            Vector4::new(0.15346, -0.26756, 0.06670, 0.26688),
+        );
+        let at_zenith = perez(&self.coefficients(), 1.0, theta);
+        [luminance / at_zenith[0], x / at_zenith[1], y / at_zenith[2]]
+    }
+
+    // The sky in `direction`, after the same exposure curve as in `sky.wgsl`.
+    pub fn radiance(&self, direction: Vector3<f32>) -> [f32; 3] {
+        let gamma = direction
+            .dot(self.model_sun_direction())
+            .clamp(-1.0, 1.0)
+            .acos();
+        let f = perez(&self.coefficients(), direction.y, gamma);
+        let zenith = self.zenith();
+        let rgb = yxy_to_rgb([
+            zenith[0] * f[0] * EXPOSURE * self.daylight(),
+            zenith[1] * f[1],
+            zenith[2] * f[2],
+        ]);
+        let mut color = [0.0; 3];
+        for (i, value) in color.iter_mut().enumerate() {
+            *value = (1.0 - (-rgb[i].max(0.0)).exp()).max(NIGHT_SKY[i]);
+        }
+        color
+    }
+
+    // The sunlight left after passing through the atmosphere: Rayleigh scattering by the
+    // air and Mie scattering by the haze, over Kasten and Young's relative air mass.
+    pub fn sun_color(&self) -> [f32; 3] {
+        let sun = self.sun_direction();
+        if sun.y <= 0.0 {
+            return [0.0, 0.0, 0.0];
+        }
+        let elevation = sun.y.asin().to_degrees();
+        let air_mass = 1.0 / (sun.y + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+        // The haze as in the Preetham paper, with an Angstrom exponent of 1.3.
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let mut color = [0.0; 3];
+        for (value, &wavelength) in color.iter_mut().zip(&[0.680f32, 0.550, 0.440]) {
+            let rayleigh = 0.0088 * wavelength.powf(-4.05);
+            let mie = beta * wavelength.powf(-1.3);
+            *value = (-(rayleigh + mie) * air_mass).exp();
+        }
+        color
+    }
+
+    // The hemisphere light from above: the sky averaged over a ring of directions halfway
+    // up and the zenith.
+    pub fn sky_color(&self) -> [f32; 3] {
+        let mut directions = vec![Vector3::unit_y()];
+        for i in 0..8 {
+            let azimuth = i as f32 / 8.0 * 2.0 * PI;
+            directions.push(Vector3::new(azimuth.cos(), 1.0, azimuth.sin()).normalize());
+        }
+        let mut color = [0.0; 3];
+        for direction in &directions {
+            let radiance = self.radiance(*direction);
+            for (value, channel) in color.iter_mut().zip(&radiance) {
+                *value += channel / directions.len() as f32;
+            }
+        }
+        color
+    }
+
+    // The hemisphere light from below: the sun and the sky reflected by the ground.
+    pub fn ground_color(&self) -> [f32; 3] {
+        let sun = self.sun_color();
+        let sky = self.sky_color();
+        let irradiance = self.sun_direction().y.max(0.0);
+        let mut color = [0.0; 3];
+        for (i, value) in color.iter_mut().enumerate() {
+            *value = GROUND_ALBEDO * (sun[i] * irradiance + sky[i]);
+        }
+        color
+    }
+
+    // Takes a point on the far plane in normalized device coordinates to the direction
+    // through it, so the fourth column is left empty.
+    fn ray_matrix(width: u32, height: u32) -> [[f32; 4]; 4] {
+        let tan = (FIELD_OF_VIEW.to_radians() / 2.0).tan();
+        let aspect = width as f32 / height.max(1) as f32;
+        let pitch = PITCH.to_radians();
+        let right = Vector3::unit_x() * tan * aspect;
+        let up = Vector3::new(0.0, pitch.cos(), -pitch.sin()) * tan;
+        let forward = Vector3::new(0.0, pitch.sin(), pitch.cos());
+        Matrix4::from_cols(
+            right.extend(0.0),
+            up.extend(0.0),
+            forward.extend(0.0),
+            Vector4::new(0.0, 0.0, 0.0, 0.0),
+        )
+        .into()
+    }
+
+    pub fn uniform(&self, width: u32, height: u32) -> SkyUniform {
+        let [a, b, c, d, e] = self.coefficients();
+        SkyUniform {
+            _ray_matrix: Self::ray_matrix(width, height),
+            _a: a,
+            _exposure: EXPOSURE * self.daylight(),
+            _b: b,
+            _pad0: 0.0,
+            _c: c,
+            _pad1: 0.0,
+            _d: d,
+            _pad2: 0.0,
+            _e: e,
+            _pad3: 0.0,
+            _zenith: self.zenith(),
+            _pad4: 0.0,
+            _sun_direction: self.model_sun_direction().into(),
+            _pad5: 0.0,
+            _sun_color: self.sun_color(),
+            _pad6: 0.0,
+            _ground_color: self.ground_color(),
+            _pad7: 0.0,
+        }
+    }
+
+    pub fn sun_light(&self) -> Light {
+        Light::Directional {
+            direction: (-self.sun_direction()).into(),
+            color: self.sun_color(),
+        }
+    }
+
+    // The ground normal stays straight up, toward the zenith.
+    pub fn hemisphere_light(&self) -> Light {
+        Light::Hemisphere {
+            sky_color: self.sky_color(),
+            ground_color: self.ground_color(),
+            up: [0.0, 1.0, 0.0],
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let minutes = (self.time_of_day * 60.0).round() as u32 % (24 * 60);
+        format!(
+            "{:02}:{:02}, turbidity {:.1}",
+            minutes / 60,
+            minutes % 60,
+            self.turbidity
+        )
+    }
+}
+
+// Lets a sample switch its own directional light, which has to be the first in its light list,
+// over to the sun, and adds the sky and ground as a hemisphere light at the end of the list on
+// top of its ambient light.
+pub struct SkyLighting {
+    pub sky: Sky,
+    enabled: bool,
+    // Put back when the sky is switched off.
+    own_light: Light,
+}
+
+impl SkyLighting {
+    pub fn new(lights: &LightSet) -> Self {
+        Self {
+            sky: Sky::new(),
+            enabled: false,
+            own_light: lights.get(0),
+        }
+    }
+
+    // L switches between the sample's own light and the sky, Comma and Period move the time of
+    // day back and forth by half an hour. Returns whether the key was one of them, in which case
+    // `lights` has been changed to match and needs an update.
+    pub fn handle_key(&mut self, keycode: VirtualKeyCode, lights: &mut LightSet) -> bool {
+        match keycode {
+            VirtualKeyCode::L => {
+                self.enabled = !self.enabled;
+                if self.enabled {
+                    lights.push(self.sky.hemisphere_light());
+                } else {
+                    lights.pop();
+                }
+            }
+            VirtualKeyCode::Comma => self.sky.advance(-0.5),
+            VirtualKeyCode::Period => self.sky.advance(0.5),
+            _ => return false,
+        }
+        if self.enabled {
+            lights.set(0, self.sky.sun_light());
+            lights.set(lights.len() - 1, self.sky.hemisphere_light());
+        } else {
+            lights.set(0, self.own_light);
+        }
+        true
+    }
+
+    pub fn title(&self) -> String {
+        if self.enabled {
+            format!("sky at {}", self.sky.title())
+        } else {
+            "own light".to_string()
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn perez(coefficients: &[[f32; 3]; 5], cos_theta: f32, gamma: f32) -> [f32; 3] {
+    let [a, b, c, d, e] = coefficients;
+    let cos_gamma = gamma.cos();
+    let mut f = [0.0; 3];
+    for (i, value) in f.iter_mut().enumerate() {
+        let horizon = 1.0 + a[i] * (b[i] / cos_theta.max(0.01)).exp();
+        let circumsolar = 1.0 + c[i] * (d[i] * gamma).exp() + e[i] * cos_gamma * cos_gamma;
+        *value = horizon * circumsolar;
+    }
+    f
+}
+
+fn yxy_to_rgb([luminance, x, y]: [f32; 3]) -> [f32; 3] {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    [
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    ]
+}
@@ -0,0 +1,104 @@
+// Packs ambient occlusion, roughness and metallic maps into the R, G and B channels of one
+// texture, the layout glTF uses for its ORM textures.
+//
+//     cargo run --bin pack_orm -- <occlusion> <roughness> <metallic> <output.png>
+//
+// Each input is either an image path, an image path with a channel suffix such as
+// `mask.png:a`, or a constant between 0 and 1. Without a suffix the luminance is used.
+
+use anyhow::{bail, Context, Result};
+use image::{GenericImageView, ImageBuffer, Luma, Rgb};
+
+enum Source {
+    Image(ImageBuffer<Luma<u8>, Vec<u8>>),
+    Constant(u8),
+}
+
+impl Source {
+    fn load(arg: &str) -> Result<Self> {
+        if let Ok(value) = arg.parse::<f32>() {
+            return Ok(Source::Constant(
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            ));
+        }
+
+        let (path, channel) = match arg.rsplit_once(':') {
+            Some((path, channel)) if channel.len() == 1 => (path, Some(channel)),
+            _ => (arg, None),
+        };
+        let img = image::open(path).with_context(|| format!("Failed to open {}", path))?;
+
+        let channel = match channel {
+            None => return Ok(Source::Image(img.to_luma8())),
+            Some("r") => 0,
+            Some("g") => 1,
+            Some("b") => 2,
+            Some("a") => 3,
+            Some(channel) => bail!("Unknown channel `{}`, expected r, g, b or a", channel),
+        };
+        let rgba = img.to_rgba8();
+        let (width, height) = img.dimensions();
+        Ok(Source::Image(ImageBuffer::from_fn(
+            width,
+            height,
+            |x, y| Luma([rgba.get_pixel(x, y)[channel]]),
+        )))
+    }
+
+    fn dimensions(&self) -> Option<(u32, u32)> {
+        match self {
+            Source::Image(img) => Some(img.dimensions()),
+            Source::Constant(_) => None,
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> u8 {
+        match self {
+            Source::Image(img) => img.get_pixel(x, y)[0],
+            Source::Constant(value) => *value,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.len() != 4 {
+        bail!("Usage: pack_orm <occlusion> <roughness> <metallic> <output.png>");
+    }
+
+    let sources = args[..3]
+        .iter()
+        .map(|arg| Source::load(arg))
+        .collect::<Result<Vec<_>>>()?;
+
+    // All images must agree on the size. Constants stretch to fit, and a 1x1 texture is
+    // written when every input is a constant.
+    let mut dimensions = None;
+    for (arg, size) in args.iter().zip(sources.iter().map(Source::dimensions)) {
+        match (dimensions, size) {
+            (None, Some(size)) => dimensions = Some(size),
+            (Some(expected), Some(size)) if expected != size => bail!(
+                "{} is {}x{} but the other inputs are {}x{}",
+                arg,
+                size.0,
+                size.1,
+                expected.0,
+                expected.1
+            ),
+            _ => {}
+        }
+    }
+    let (width, height) = dimensions.unwrap_or((1, 1));
+
+    let orm = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgb([
+            sources[0].get(x, y),
+            sources[1].get(x, y),
+            sources[2].get(x, y),
+        ])
+    });
+    orm.save(&args[3])
+        .with_context(|| format!("Failed to write {}", args[3]))?;
+
+    Ok(())
+}
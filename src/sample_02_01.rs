@@ -23,6 +23,16 @@ fn create_vertices() -> Vec<Vertex> {
     vec![vertex(-1.0, -1.0), vertex(0.0, 1.0), vertex(1.0, -1.0)]
 }
 
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
 async fn run(event_loop: EventLoop<()>, window: Window) {
     let size = window.inner_size();
     let instance = wgpu::Instance::new(wgpu::BackendBit::all());
@@ -62,7 +72,8 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
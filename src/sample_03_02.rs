@@ -29,6 +29,16 @@ fn create_vertices() -> Vec<Vertex> {
     ]
 }
 
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
 async fn run(event_loop: EventLoop<()>, window: Window) {
     let size = window.inner_size();
     let instance = wgpu::Instance::new(wgpu::BackendBit::all());
@@ -68,6 +78,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         &queue,
         diffuse_bytes,
         "rustacean-orig-noshadow.png",
+        texture::TextureRole::Color,
     )
     .unwrap();
 
@@ -118,7 +129,8 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -236,7 +248,10 @@ fn main() {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
@@ -247,15 +262,30 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
     impl Texture {
         pub fn from_bytes(
             device: &wgpu::Device,
             queue: &wgpu::Queue,
             bytes: &[u8],
             label: &str,
+            role: TextureRole,
         ) -> Result<Self> {
             let img = image::load_from_memory(bytes)?;
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), role)
         }
 
         pub fn from_image(
@@ -263,6 +293,7 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
             let rgba = img.as_rgba8().unwrap();
             let dimensions = img.dimensions();
@@ -272,14 +303,18 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -297,14 +332,20 @@ mod texture {
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -314,5 +355,115 @@ mod texture {
                 sampler,
             })
         }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
     }
 }
@@ -3,27 +3,43 @@ use cgmath::{Matrix4, Quaternion};
 use std::{borrow::Cow, mem::size_of};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+#[path = "common/fog.rs"]
+mod fog;
+#[path = "common/hdr.rs"]
+mod hdr;
+#[path = "common/light.rs"]
+mod light;
+#[path = "common/sky.rs"]
+mod sky;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct Matrix {
     _matrix: [[f32; 4]; 4],
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct DirectionLight {
-    _eye_position: [f32; 3],
-    _pad0: f32,
-    _directional_light_direction: [f32; 3],
-    _pad1: f32,
-    _directianal_light_color: [f32; 3],
-    _pad2: f32,
-    _ambient_color: [f32; 3],
+fn title(hdr: &hdr::HdrPipeline, fog: &fog::Fog, sky_lighting: &sky::SkyLighting) -> String {
+    format!(
+        "sample_04_04: {} (T, PageUp/PageDown, A), {} (F, H, -/=), {} (L, Comma/Period)",
+        hdr.title(),
+        fog.title(),
+        sky_lighting.title()
+    )
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
 }
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
@@ -111,7 +127,11 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // Load the shaders from disk
     let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/04_04.wgsl"))),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/04_04.wgsl")
+        ))),
         flags: wgpu::ShaderFlags::all(),
     });
 
@@ -169,18 +189,9 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: None,
         });
 
-    let directional_light_bind_group_layout =
+    let light_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
+            entries: &[light::LightSet::layout_entry(0), fog::Fog::layout_entry(5)],
             label: None,
         });
 
@@ -206,33 +217,33 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         label: None,
     });
 
-    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("light"),
-        contents: bytemuck::cast_slice(&[DirectionLight {
-            _eye_position: [0.0, 0.0, 0.0],
-            _pad0: 0.0,
-            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
-                1.0f32, -1.0, 1.0,
-            ))
-            .into(),
-            _pad1: 0.0,
-            _directianal_light_color: [0.5, 0.5, 0.5],
-            _pad2: 0.0,
-            _ambient_color: [0.3, 0.3, 0.3],
-        }]),
-        usage: wgpu::BufferUsage::UNIFORM,
+    let mut lights = light::LightSet::new(&device, [0.0, 0.0, 0.0], [0.3, 0.3, 0.3]);
+    lights.push(light::Light::Directional {
+        direction: [1.0, -1.0, 1.0],
+        color: [0.5, 0.5, 0.5],
     });
+    lights.update(&device, &queue);
+    let mut sky_lighting = sky::SkyLighting::new(&lights);
 
-    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &directional_light_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &directional_light_buffer,
-                offset: 0,
-                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
-            }),
-        }],
+    let mut fog = fog::Fog::new_clip_space();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: fog_buffer.as_entire_binding(),
+            },
+        ],
         label: None,
     });
 
@@ -242,12 +253,13 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             &uniform_bind_group_layout,
             &local_bind_group_layout,
             &texture_bind_group_layout,
-            &directional_light_bind_group_layout,
+            &light_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<loader::Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -289,7 +301,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[swapchain_format.into()],
+            targets: &[hdr::HDR_FORMAT.into()],
         }),
         primitive: wgpu::PrimitiveState {
             cull_mode: Some(wgpu::Face::Front),
@@ -318,6 +330,9 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+    let mut hdr = hdr::HdrPipeline::new(&device, &queue, &sc_desc);
+    window.set_title(&title(&hdr, &fog, &sky_lighting));
+
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
@@ -336,7 +351,36 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 swap_chain = device.create_swap_chain(&surface, &sc_desc);
                 depth_texture =
                     texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                hdr.resize(&device, &sc_desc);
             }
+            // The keys are handled by `HdrPipeline::handle_key`, `Fog::handle_key` and
+            // `SkyLighting::handle_key`.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                if !hdr.handle_key(keycode)
+                    && !fog.handle_key(keycode)
+                    && !sky_lighting.handle_key(keycode, &mut lights)
+                {
+                    return;
+                }
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                lights.update(&device, &queue);
+                window.set_title(&title(&hdr, &fog, &sky_lighting));
+                window.request_redraw();
+            }
+            // Eye adaptation converges over several frames, so keep drawing while it is on.
+            Event::MainEventsCleared if hdr.eye_adaptation => window.request_redraw(),
             Event::RedrawRequested(_) => {
                 let frame = swap_chain
                     .get_current_frame()
@@ -358,7 +402,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &frame.view,
+                            view: hdr.view(),
                             resolve_target: None,
                             ops: wgpu::Operations {
                                 load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
@@ -376,7 +420,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
                     rpass.set_pipeline(&render_pipeline);
                     rpass.set_bind_group(0, &uniform_bind_group, &[]);
-                    rpass.set_bind_group(3, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &light_bind_group, &[]);
                     for (i, primitive) in scene.primitives.iter().enumerate() {
                         rpass.set_bind_group(
                             1,
@@ -399,6 +443,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     }
                 }
 
+                hdr.render(&queue, &mut encoder, &frame.view);
                 queue.submit(Some(encoder.finish()));
             }
             Event::WindowEvent {
@@ -474,8 +519,14 @@ mod loader {
                         }
                         .unwrap();
 
-                        let diffuse_texture =
-                            texture::Texture::from_image(device, queue, &image, None).unwrap();
+                        let diffuse_texture = texture::Texture::from_image(
+                            device,
+                            queue,
+                            &image,
+                            None,
+                            texture::TextureRole::Color,
+                        )
+                        .unwrap();
 
                         let diffuse_bind_group =
                             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -593,7 +644,10 @@ mod loader {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
@@ -604,6 +658,20 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
     impl Texture {
         pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -654,7 +722,7 @@ mod texture {
 
         pub fn dummy(device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Self> {
             let img = image::DynamicImage::new_rgba8(16, 16);
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), TextureRole::Color)
         }
 
         pub fn from_image(
@@ -662,6 +730,7 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
             let rgba = img.to_rgba8().to_vec();
             let dimensions = img.dimensions();
@@ -671,14 +740,18 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -696,14 +769,20 @@ mod texture {
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -713,5 +792,115 @@ mod texture {
                 sampler,
             })
         }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
     }
 }
@@ -1,13 +1,22 @@
 use bytemuck::{Pod, Zeroable};
-use cgmath::{Matrix4, Quaternion};
+use cgmath::{Matrix3, Matrix4, Quaternion, Vector3};
 use std::{borrow::Cow, mem::size_of};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+#[path = "common/fog.rs"]
+mod fog;
+#[path = "common/hdr.rs"]
+mod hdr;
+#[path = "common/light.rs"]
+mod light;
+#[path = "common/sky.rs"]
+mod sky;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct Matrix {
@@ -16,19 +25,67 @@ struct Matrix {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct DirectionLight {
-    _eye_position: [f32; 3],
-    _pad0: f32,
-    _directional_light_direction: [f32; 3],
-    _pad1: f32,
-    _directianal_light_color: [f32; 3],
-    _pad2: f32,
-    _ambient_color: [f32; 3],
-    _pad3: f32,
-    _point_light_position: [f32; 3],
-    _pad4: f32,
-    _point_light_color: [f32; 3],
-    _point_light_range: f32,
+struct PointShadow {
+    _near: f32,
+    _far: f32,
+    _bias: f32,
+    _soft: u32,
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const POINT_LIGHT_POSITION: [f32; 3] = [0.0, 0.0, -4.0];
+const POINT_LIGHT_RANGE: f32 = 12.0;
+const SHADOW_MAP_SIZE: u32 = 1024;
+const SHADOW_NEAR: f32 = 0.05;
+
+// The view from `position` through one face of a cube map, in the order +X, -X, +Y, -Y, +Z,
+// -Z. The axes follow the cube map sampling rules instead of `look_at`, whose images come out
+// mirrored, so a direction finds the depth that was rendered for it.
+fn cube_face_view(face: usize, position: Vector3<f32>) -> Matrix4<f32> {
+    // The directions of the face's u and v axes and the direction it looks at.
+    let (u, v, forward) = match face {
+        0 => (-Vector3::unit_z(), -Vector3::unit_y(), Vector3::unit_x()),
+        1 => (Vector3::unit_z(), -Vector3::unit_y(), -Vector3::unit_x()),
+        2 => (Vector3::unit_x(), Vector3::unit_z(), Vector3::unit_y()),
+        3 => (Vector3::unit_x(), -Vector3::unit_z(), -Vector3::unit_y()),
+        4 => (Vector3::unit_x(), -Vector3::unit_y(), Vector3::unit_z()),
+        _ => (-Vector3::unit_x(), -Vector3::unit_y(), -Vector3::unit_z()),
+    };
+    // v grows downwards while clip space y grows upwards, and the camera looks down -z.
+    let rotation = cgmath::Matrix::transpose(&Matrix3::from_cols(u, -v, -forward));
+    Matrix4::from(rotation) * Matrix4::from_translation(-position)
+}
+
+fn point_shadow_title(
+    soft_shadows: bool,
+    hdr: &hdr::HdrPipeline,
+    fog: &fog::Fog,
+    sky_lighting: &sky::SkyLighting,
+) -> String {
+    format!(
+        "sample_05_01: {} shadows (S to switch), {} (T, PageUp/PageDown, A), {} (F, H, -/=), {} (L, Comma/Period)",
+        if soft_shadows { "soft" } else { "hard" },
+        hdr.title(),
+        fog.title(),
+        sky_lighting.title()
+    )
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
 }
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
@@ -116,21 +173,25 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // Load the shaders from disk
     let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/05_01.wgsl"))),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/05_01.wgsl")
+        ))),
         flags: wgpu::ShaderFlags::all(),
     });
 
+    let global_matrix = Matrix4::from_translation(cgmath::vec3(0.0, 0.0, 0.5))
+        * Matrix4::from_scale(0.01)
+        * Matrix4::from(Quaternion::from(cgmath::Euler {
+            x: cgmath::Rad(0.0),
+            y: cgmath::Rad(0.0),
+            z: cgmath::Rad(0.0),
+        }));
     let global_matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("global matrix"),
         contents: bytemuck::cast_slice(&[Matrix {
-            _matrix: (Matrix4::from_translation(cgmath::vec3(0.0, 0.0, 0.5))
-                * Matrix4::from_scale(0.01)
-                * Matrix4::from(Quaternion::from(cgmath::Euler {
-                    x: cgmath::Rad(0.0),
-                    y: cgmath::Rad(0.0),
-                    z: cgmath::Rad(0.0),
-                })))
-            .into(),
+            _matrix: global_matrix.into(),
         }]),
         usage: wgpu::BufferUsage::UNIFORM,
     });
@@ -174,18 +235,43 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: None,
         });
 
-    let directional_light_bind_group_layout =
+    // The shadow map shares the light's group, as the other three groups are taken and four
+    // is the default limit.
+    let light_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                light::LightSet::layout_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: true,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                fog::Fog::layout_entry(5),
+            ],
             label: None,
         });
 
@@ -211,38 +297,105 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         label: None,
     });
 
-    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("light"),
-        contents: bytemuck::cast_slice(&[DirectionLight {
-            _eye_position: [0.0, 0.0, 0.0],
-            _pad0: 0.0,
-            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
-                1.0f32, -1.0, 1.0,
-            ))
-            .into(),
-            _pad1: 0.0,
-            _directianal_light_color: [0.5, 0.5, 0.5],
-            _pad2: 0.0,
-            _ambient_color: [0.3, 0.3, 0.3],
-            _pad3: 0.0,
-            _point_light_position: [0.0, 0.0, -4.0],
-            _pad4: 0.0,
-            _point_light_color: [1.0, 0.0, 0.0],
-            _point_light_range: 12.0,
-        }]),
-        usage: wgpu::BufferUsage::UNIFORM,
+    let mut lights = light::LightSet::new(&device, [0.0, 0.0, 0.0], [0.3, 0.3, 0.3]);
+    lights.push(light::Light::Directional {
+        direction: [1.0, -1.0, 1.0],
+        color: [0.5, 0.5, 0.5],
     });
+    // `SHADOWED_LIGHT` in the shader, the light the cube shadow map is rendered from.
+    lights.push(light::Light::Point {
+        position: POINT_LIGHT_POSITION,
+        color: [1.0, 0.0, 0.0],
+        range: POINT_LIGHT_RANGE,
+    });
+    lights.update(&device, &queue);
+    let mut sky_lighting = sky::SkyLighting::new(&lights);
 
-    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &directional_light_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &directional_light_buffer,
-                offset: 0,
-                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
-            }),
-        }],
+    let mut fog = fog::Fog::new_clip_space();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    // The point light renders the scene into all six faces of a cube map with a 90 degree
+    // projection each. The shader compares against the depth such a projection gives along the
+    // major axis of the direction to the light.
+    let mut soft_shadows = true;
+    let point_shadow = |soft_shadows: bool| PointShadow {
+        _near: SHADOW_NEAR,
+        _far: POINT_LIGHT_RANGE,
+        _bias: 0.02,
+        _soft: soft_shadows as u32,
+    };
+    let shadow_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("point shadow"),
+        contents: bytemuck::bytes_of(&point_shadow(soft_shadows)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+    let shadow_map = texture::Texture::create_shadow_cube(&device, SHADOW_MAP_SIZE, "shadow cube");
+    let shadow_face_views = (0..6)
+        .map(|face| shadow_map.face_view(face))
+        .collect::<Vec<_>>();
+
+    let shadow_face_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("shadow faces"),
+        size: 6 * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let face_projection = OPENGL_TO_WGPU_MATRIX
+        * cgmath::perspective(cgmath::Deg(90.0), 1.0, SHADOW_NEAR, POINT_LIGHT_RANGE);
+    let shadow_face_bind_groups = (0..6)
+        .map(|face| {
+            let offset = face as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT;
+            let view = cube_face_view(face, Vector3::from(POINT_LIGHT_POSITION));
+            queue.write_buffer(
+                &shadow_face_buffer,
+                offset,
+                bytemuck::bytes_of(&Matrix {
+                    _matrix: (face_projection * view * global_matrix).into(),
+                }),
+            );
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &shadow_face_buffer,
+                        offset,
+                        size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+                    }),
+                }],
+                label: Some("shadow_face_bind_group"),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadow_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: fog_buffer.as_entire_binding(),
+            },
+        ],
         label: None,
     });
 
@@ -252,12 +405,13 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             &uniform_bind_group_layout,
             &local_bind_group_layout,
             &texture_bind_group_layout,
-            &directional_light_bind_group_layout,
+            &light_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<loader::Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -299,7 +453,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[swapchain_format.into()],
+            targets: &[hdr::HDR_FORMAT.into()],
         }),
         primitive: wgpu::PrimitiveState {
             cull_mode: Some(wgpu::Face::Front),
@@ -315,6 +469,46 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         multisample: wgpu::MultisampleState::default(),
     });
 
+    // Only depth is written, so there is no fragment stage. The shadow pass sees the teapot
+    // from the light, so both faces are drawn instead of guessing the winding.
+    let shadow_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("shadow"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/shadow.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let shadow_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("shadow"),
+        bind_group_layouts: &[&uniform_bind_group_layout, &local_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("shadow"),
+        layout: Some(&shadow_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shadow_shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: vertex_size as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 0,
+                }],
+            }],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
     let mut sc_desc = wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
         format: swapchain_format,
@@ -328,11 +522,21 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+    let mut hdr = hdr::HdrPipeline::new(&device, &queue, &sc_desc);
+    window.set_title(&point_shadow_title(soft_shadows, &hdr, &fog, &sky_lighting));
+
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
-        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &pipeline_layout,
+            &shadow_shader,
+            &shadow_pipeline_layout,
+        );
 
         *control_flow = ControlFlow::Wait;
         match event {
@@ -346,7 +550,48 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 swap_chain = device.create_swap_chain(&surface, &sc_desc);
                 depth_texture =
                     texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                hdr.resize(&device, &sc_desc);
             }
+            // S switches between hard and filtered shadow edges, the other keys are handled by
+            // `HdrPipeline::handle_key`, `Fog::handle_key` and `SkyLighting::handle_key`.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::S => {
+                        soft_shadows = !soft_shadows;
+                        queue.write_buffer(
+                            &shadow_buffer,
+                            0,
+                            bytemuck::bytes_of(&point_shadow(soft_shadows)),
+                        );
+                    }
+                    _ => {
+                        if !hdr.handle_key(keycode)
+                            && !fog.handle_key(keycode)
+                            && !sky_lighting.handle_key(keycode, &mut lights)
+                        {
+                            return;
+                        }
+                    }
+                }
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                lights.update(&device, &queue);
+                window.set_title(&point_shadow_title(soft_shadows, &hdr, &fog, &sky_lighting));
+                window.request_redraw();
+            }
+            // Eye adaptation converges over several frames, so keep drawing while it is on.
+            Event::MainEventsCleared if hdr.eye_adaptation => window.request_redraw(),
             Event::RedrawRequested(_) => {
                 let frame = swap_chain
                     .get_current_frame()
@@ -365,10 +610,45 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         );
                     }
 
+                    for (face_view, face_bind_group) in
+                        shadow_face_views.iter().zip(&shadow_face_bind_groups)
+                    {
+                        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("shadow"),
+                            color_attachments: &[],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: face_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: true,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                        rpass.set_pipeline(&shadow_pipeline);
+                        rpass.set_bind_group(0, face_bind_group, &[]);
+                        for (i, primitive) in scene.primitives.iter().enumerate() {
+                            rpass.set_bind_group(
+                                1,
+                                &local_bind_group,
+                                &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                    as wgpu::DynamicOffset],
+                            );
+                            rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                            rpass.set_index_buffer(
+                                primitive.index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint32,
+                            );
+                            rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                        }
+                    }
+
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &frame.view,
+                            view: hdr.view(),
                             resolve_target: None,
                             ops: wgpu::Operations {
                                 load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
@@ -386,7 +666,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
                     rpass.set_pipeline(&render_pipeline);
                     rpass.set_bind_group(0, &uniform_bind_group, &[]);
-                    rpass.set_bind_group(3, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &light_bind_group, &[]);
                     for (i, primitive) in scene.primitives.iter().enumerate() {
                         rpass.set_bind_group(
                             1,
@@ -409,6 +689,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     }
                 }
 
+                hdr.render(&queue, &mut encoder, &frame.view);
                 queue.submit(Some(encoder.finish()));
             }
             Event::WindowEvent {
@@ -484,8 +765,14 @@ mod loader {
                         }
                         .unwrap();
 
-                        let diffuse_texture =
-                            texture::Texture::from_image(device, queue, &image, None).unwrap();
+                        let diffuse_texture = texture::Texture::from_image(
+                            device,
+                            queue,
+                            &image,
+                            None,
+                            texture::TextureRole::Color,
+                        )
+                        .unwrap();
 
                         let diffuse_bind_group =
                             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -603,7 +890,10 @@ mod loader {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
@@ -614,6 +904,20 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
     impl Texture {
         pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -662,9 +966,59 @@ mod texture {
             }
         }
 
+        // A depth cube map for point light shadows. Each face is rendered through its own
+        // view, see `face_view`, and the whole cube is sampled through `view`.
+        pub fn create_shadow_cube(device: &wgpu::Device, size: u32, label: &str) -> Self {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+            }
+        }
+
+        // A view of a single cube face to render into.
+        pub fn face_view(&self, face: u32) -> wgpu::TextureView {
+            self.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("shadow cube face"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        }
+
         pub fn dummy(device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Self> {
             let img = image::DynamicImage::new_rgba8(16, 16);
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), TextureRole::Color)
         }
 
         pub fn from_image(
@@ -672,6 +1026,7 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
             let rgba = img.to_rgba8().to_vec();
             let dimensions = img.dimensions();
@@ -681,14 +1036,18 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -706,14 +1065,20 @@ mod texture {
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -723,5 +1088,115 @@ mod texture {
                 sampler,
             })
         }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
     }
 }
@@ -1,13 +1,22 @@
 use bytemuck::{Pod, Zeroable};
-use cgmath::{Matrix4, Quaternion};
+use cgmath::{Matrix4, Point3, Quaternion, Vector3};
 use std::{borrow::Cow, f32::consts::PI, mem::size_of};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+#[path = "common/fog.rs"]
+mod fog;
+#[path = "common/hdr.rs"]
+mod hdr;
+#[path = "common/light.rs"]
+mod light;
+#[path = "common/sky.rs"]
+mod sky;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct Matrix {
@@ -16,22 +25,69 @@ struct Matrix {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct DirectionLight {
-    _eye_position: [f32; 3],
-    _pad0: f32,
-    _directional_light_direction: [f32; 3],
-    _pad1: f32,
-    _directianal_light_color: [f32; 3],
-    _pad2: f32,
-    _ambient_color: [f32; 3],
-    _pad3: f32,
-    _spot_light_position: [f32; 3],
-    _pad4: f32,
-    _spot_light_color: [f32; 3],
-    _pad5: f32,
-    _spot_light_direction: [f32; 3],
-    _spot_light_angle: f32,
-    _spot_light_range: f32,
+struct SpotShadow {
+    _light_view_projection: [[f32; 4]; 4],
+    _bias: f32,
+    _soft: u32,
+    _texel_size: f32,
+    _pad: f32,
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const SPOT_LIGHT_POSITION: [f32; 3] = [0.0, 0.3, -4.0];
+const SPOT_LIGHT_DIRECTION: [f32; 3] = [0.0, 0.0, 1.0];
+const SPOT_LIGHT_ANGLE: f32 = PI * 0.1;
+const SPOT_LIGHT_RANGE: f32 = 120.0;
+const SHADOW_MAP_SIZE: u32 = 1024;
+
+// A perspective projection from the spot light whose frustum just contains its cone.
+fn spot_light_view_projection() -> Matrix4<f32> {
+    let direction = Vector3::from(SPOT_LIGHT_DIRECTION);
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+    let view = Matrix4::look_to_rh(Point3::from(SPOT_LIGHT_POSITION), direction, up);
+    let projection = cgmath::perspective(
+        cgmath::Rad(2.0 * SPOT_LIGHT_ANGLE),
+        1.0,
+        0.05,
+        SPOT_LIGHT_RANGE,
+    );
+    OPENGL_TO_WGPU_MATRIX * projection * view
+}
+
+fn spot_shadow_title(
+    soft_shadows: bool,
+    hdr: &hdr::HdrPipeline,
+    fog: &fog::Fog,
+    sky_lighting: &sky::SkyLighting,
+) -> String {
+    format!(
+        "sample_05_02: {} shadows (S to switch), {} (T, PageUp/PageDown, A), {} (F, H, -/=), {} (L, Comma/Period)",
+        if soft_shadows { "soft" } else { "hard" },
+        hdr.title(),
+        fog.title(),
+        sky_lighting.title()
+    )
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
 }
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
@@ -119,21 +175,25 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // Load the shaders from disk
     let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/05_02.wgsl"))),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/05_02.wgsl")
+        ))),
         flags: wgpu::ShaderFlags::all(),
     });
 
+    let global_matrix = Matrix4::from_translation(cgmath::vec3(0.0, 0.0, 0.5))
+        * Matrix4::from_scale(0.01)
+        * Matrix4::from(Quaternion::from(cgmath::Euler {
+            x: cgmath::Rad(0.0),
+            y: cgmath::Rad(0.0),
+            z: cgmath::Rad(0.0),
+        }));
     let global_matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("global matrix"),
         contents: bytemuck::cast_slice(&[Matrix {
-            _matrix: (Matrix4::from_translation(cgmath::vec3(0.0, 0.0, 0.5))
-                * Matrix4::from_scale(0.01)
-                * Matrix4::from(Quaternion::from(cgmath::Euler {
-                    x: cgmath::Rad(0.0),
-                    y: cgmath::Rad(0.0),
-                    z: cgmath::Rad(0.0),
-                })))
-            .into(),
+            _matrix: global_matrix.into(),
         }]),
         usage: wgpu::BufferUsage::UNIFORM,
     });
@@ -177,18 +237,43 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: None,
         });
 
-    let directional_light_bind_group_layout =
+    // The shadow map shares the light's group, as the other three groups are taken and four
+    // is the default limit.
+    let light_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                light::LightSet::layout_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: true,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                fog::Fog::layout_entry(5),
+            ],
             label: None,
         });
 
@@ -214,41 +299,88 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         label: None,
     });
 
-    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("light"),
-        contents: bytemuck::cast_slice(&[DirectionLight {
-            _eye_position: [0.0, 0.0, 0.0],
-            _pad0: 0.0,
-            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
-                1.0f32, -1.0, 1.0,
-            ))
-            .into(),
-            _pad1: 0.0,
-            _directianal_light_color: [0.5, 0.5, 0.5],
-            _pad2: 0.0,
-            _ambient_color: [0.3, 0.3, 0.3],
-            _pad3: 0.0,
-            _spot_light_position: [0.0, 0.3, -4.0],
-            _pad4: 0.0,
-            _spot_light_color: [100.0, 0.0, 0.0],
-            _pad5: 0.0,
-            _spot_light_direction: [0.0, 0.0, 1.0],
-            _spot_light_angle: PI * 0.1,
-            _spot_light_range: 120.0,
-        }]),
-        usage: wgpu::BufferUsage::UNIFORM,
+    let mut lights = light::LightSet::new(&device, [0.0, 0.0, 0.0], [0.3, 0.3, 0.3]);
+    lights.push(light::Light::Directional {
+        direction: [1.0, -1.0, 1.0],
+        color: [0.5, 0.5, 0.5],
+    });
+    // `SHADOWED_LIGHT` in the shader, the light the shadow map is rendered from.
+    lights.push(light::Light::Spot {
+        position: SPOT_LIGHT_POSITION,
+        direction: SPOT_LIGHT_DIRECTION,
+        color: [100.0, 0.0, 0.0],
+        angle: SPOT_LIGHT_ANGLE,
+        range: SPOT_LIGHT_RANGE,
     });
+    lights.update(&device, &queue);
+    let mut sky_lighting = sky::SkyLighting::new(&lights);
 
-    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &directional_light_bind_group_layout,
+    let mut fog = fog::Fog::new_clip_space();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    // The spot light renders the scene through a frustum fitted to its cone. The shadow pass
+    // draws raw vertices, so its matrix also applies the global matrix the lighting shader has
+    // already applied to its world positions.
+    let mut soft_shadows = true;
+    let spot_shadow = |soft_shadows: bool| SpotShadow {
+        _light_view_projection: spot_light_view_projection().into(),
+        _bias: 0.02,
+        _soft: soft_shadows as u32,
+        _texel_size: 1.0 / SHADOW_MAP_SIZE as f32,
+        _pad: 0.0,
+    };
+    let shadow_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("spot shadow"),
+        contents: bytemuck::bytes_of(&spot_shadow(soft_shadows)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+    let shadow_map = texture::Texture::create_shadow_map(&device, SHADOW_MAP_SIZE, "shadow map");
+
+    let shadow_view_projection_buffer =
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("shadow view projection"),
+            contents: bytemuck::bytes_of(&Matrix {
+                _matrix: (spot_light_view_projection() * global_matrix).into(),
+            }),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+    let shadow_view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
         entries: &[wgpu::BindGroupEntry {
             binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &directional_light_buffer,
-                offset: 0,
-                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
-            }),
+            resource: shadow_view_projection_buffer.as_entire_binding(),
         }],
+        label: Some("shadow_view_projection_bind_group"),
+    });
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadow_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: fog_buffer.as_entire_binding(),
+            },
+        ],
         label: None,
     });
 
@@ -258,12 +390,13 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             &uniform_bind_group_layout,
             &local_bind_group_layout,
             &texture_bind_group_layout,
-            &directional_light_bind_group_layout,
+            &light_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<loader::Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -305,7 +438,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[swapchain_format.into()],
+            targets: &[hdr::HDR_FORMAT.into()],
         }),
         primitive: wgpu::PrimitiveState {
             cull_mode: Some(wgpu::Face::Front),
@@ -321,6 +454,46 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         multisample: wgpu::MultisampleState::default(),
     });
 
+    // Only depth is written, so there is no fragment stage. The shadow pass sees the teapot
+    // from the light, so both faces are drawn instead of guessing the winding.
+    let shadow_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("shadow"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/shadow.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let shadow_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("shadow"),
+        bind_group_layouts: &[&uniform_bind_group_layout, &local_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("shadow"),
+        layout: Some(&shadow_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shadow_shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: vertex_size as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 0,
+                }],
+            }],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
     let mut sc_desc = wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
         format: swapchain_format,
@@ -334,11 +507,21 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+    let mut hdr = hdr::HdrPipeline::new(&device, &queue, &sc_desc);
+    window.set_title(&spot_shadow_title(soft_shadows, &hdr, &fog, &sky_lighting));
+
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
-        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &pipeline_layout,
+            &shadow_shader,
+            &shadow_pipeline_layout,
+        );
 
         *control_flow = ControlFlow::Wait;
         match event {
@@ -352,7 +535,48 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 swap_chain = device.create_swap_chain(&surface, &sc_desc);
                 depth_texture =
                     texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                hdr.resize(&device, &sc_desc);
+            }
+            // S switches between hard and filtered shadow edges, the other keys are handled by
+            // `HdrPipeline::handle_key`, `Fog::handle_key` and `SkyLighting::handle_key`.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::S => {
+                        soft_shadows = !soft_shadows;
+                        queue.write_buffer(
+                            &shadow_buffer,
+                            0,
+                            bytemuck::bytes_of(&spot_shadow(soft_shadows)),
+                        );
+                    }
+                    _ => {
+                        if !hdr.handle_key(keycode)
+                            && !fog.handle_key(keycode)
+                            && !sky_lighting.handle_key(keycode, &mut lights)
+                        {
+                            return;
+                        }
+                    }
+                }
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                lights.update(&device, &queue);
+                window.set_title(&spot_shadow_title(soft_shadows, &hdr, &fog, &sky_lighting));
+                window.request_redraw();
             }
+            // Eye adaptation converges over several frames, so keep drawing while it is on.
+            Event::MainEventsCleared if hdr.eye_adaptation => window.request_redraw(),
             Event::RedrawRequested(_) => {
                 let frame = swap_chain
                     .get_current_frame()
@@ -371,10 +595,43 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         );
                     }
 
+                    {
+                        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("shadow"),
+                            color_attachments: &[],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &shadow_map.view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: true,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                        rpass.set_pipeline(&shadow_pipeline);
+                        rpass.set_bind_group(0, &shadow_view_projection_bind_group, &[]);
+                        for (i, primitive) in scene.primitives.iter().enumerate() {
+                            rpass.set_bind_group(
+                                1,
+                                &local_bind_group,
+                                &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                    as wgpu::DynamicOffset],
+                            );
+                            rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                            rpass.set_index_buffer(
+                                primitive.index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint32,
+                            );
+                            rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                        }
+                    }
+
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &frame.view,
+                            view: hdr.view(),
                             resolve_target: None,
                             ops: wgpu::Operations {
                                 load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
@@ -392,7 +649,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
                     rpass.set_pipeline(&render_pipeline);
                     rpass.set_bind_group(0, &uniform_bind_group, &[]);
-                    rpass.set_bind_group(3, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &light_bind_group, &[]);
                     for (i, primitive) in scene.primitives.iter().enumerate() {
                         rpass.set_bind_group(
                             1,
@@ -415,6 +672,8 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     }
                 }
 
+                hdr.render(&queue, &mut encoder, &frame.view);
+
                 queue.submit(Some(encoder.finish()));
             }
             Event::WindowEvent {
@@ -490,8 +749,14 @@ mod loader {
                         }
                         .unwrap();
 
-                        let diffuse_texture =
-                            texture::Texture::from_image(device, queue, &image, None).unwrap();
+                        let diffuse_texture = texture::Texture::from_image(
+                            device,
+                            queue,
+                            &image,
+                            None,
+                            texture::TextureRole::Color,
+                        )
+                        .unwrap();
 
                         let diffuse_bind_group =
                             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -609,7 +874,10 @@ mod loader {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
@@ -620,6 +888,20 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
     impl Texture {
         pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -668,9 +950,44 @@ mod texture {
             }
         }
 
+        // A square depth texture for spot light shadows, sampled with depth comparisons.
+        pub fn create_shadow_map(device: &wgpu::Device, size: u32, label: &str) -> Self {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+            }
+        }
+
         pub fn dummy(device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Self> {
             let img = image::DynamicImage::new_rgba8(16, 16);
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), TextureRole::Color)
         }
 
         pub fn from_image(
@@ -678,6 +995,7 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
             let rgba = img.to_rgba8().to_vec();
             let dimensions = img.dimensions();
@@ -687,14 +1005,18 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -712,14 +1034,20 @@ mod texture {
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -729,5 +1057,115 @@ mod texture {
                 sampler,
             })
         }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
     }
 }
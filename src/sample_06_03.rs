@@ -3,27 +3,87 @@ use cgmath::{Matrix4, Quaternion};
 use std::{borrow::Cow, mem::size_of};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+#[path = "common/fog.rs"]
+mod fog;
+#[path = "common/hdr.rs"]
+mod hdr;
+#[path = "common/light.rs"]
+mod light;
+#[path = "common/sky.rs"]
+mod sky;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct Matrix {
     _matrix: [[f32; 4]; 4],
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct DirectionLight {
-    _eye_position: [f32; 3],
-    _pad0: f32,
-    _directional_light_direction: [f32; 3],
-    _pad1: f32,
-    _directianal_light_color: [f32; 3],
-    _pad2: f32,
-    _ambient_color: [f32; 3],
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+fn ambient_occlusion_title(
+    baked: bool,
+    ssao: &ssao::Ssao,
+    hdr: &hdr::HdrPipeline,
+    fog: &fog::Fog,
+    sky_lighting: &sky::SkyLighting,
+) -> String {
+    let ambient_occlusion = if baked {
+        "baked ambient occlusion (B)".to_string()
+    } else {
+        format!(
+            "SSAO (B), radius {:.3} (Up/Down), intensity {:.2} (Left/Right), {} samples (S)",
+            ssao.radius, ssao.intensity, ssao.sample_count
+        )
+    };
+    format!(
+        "sample_06_03: {}, {} (T, PageUp/PageDown, A), {} (F, H, -/=), {} (L, Comma/Period)",
+        ambient_occlusion,
+        hdr.title(),
+        fog.title(),
+        sky_lighting.title()
+    )
+}
+
+// Binds one source of ambient occlusion. The other slot gets a white texel so that the shader
+// can always multiply both.
+fn ambient_occlusion_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    baked: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    ssao: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(baked),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(ssao),
+            },
+        ],
+        label: Some("ambient_occlusion_bind_group"),
+    })
 }
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
@@ -78,24 +138,79 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: Some("texture_bind_group_layout"),
         });
 
-    let ambient_occlusion = include_bytes!("../assets/298186.png");
-    let ambient_occlusion_map =
-        texture::Texture::from_bytes(&device, &queue, ambient_occlusion, "Specular Map").unwrap();
+    let ambient_occlusion = include_bytes!("../assets/298186_mask.png");
+    let ambient_occlusion_map = texture::Texture::from_bytes(
+        &device,
+        &queue,
+        ambient_occlusion,
+        "Specular Map",
+        texture::TextureRole::Data,
+    )
+    .unwrap();
+
+    // The baked map and the SSAO result, only one of which is used at a time.
+    let ambient_occlusion_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("ambient_occlusion_bind_group_layout"),
+        });
 
-    let ambient_occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &texture_bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&ambient_occlusion_map.view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&ambient_occlusion_map.sampler),
+    let white = device.create_texture_with_data(
+        &queue,
+        &wgpu::TextureDescriptor {
+            label: Some("white"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
             },
-        ],
-        label: Some("diffuse_bind_group"),
-    });
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        },
+        &[255],
+    );
+    let white_view = white.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let baked_bind_group = ambient_occlusion_bind_group(
+        &device,
+        &ambient_occlusion_bind_group_layout,
+        &ambient_occlusion_map.view,
+        &ambient_occlusion_map.sampler,
+        &white_view,
+    );
 
     let teapot = include_bytes!("../assets/teapot.glb");
 
@@ -113,7 +228,11 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // Load the shaders from disk
     let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/06_03.wgsl"))),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/06_03.wgsl")
+        ))),
         flags: wgpu::ShaderFlags::all(),
     });
 
@@ -171,18 +290,9 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: None,
         });
 
-    let directional_light_bind_group_layout =
+    let light_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
+            entries: &[light::LightSet::layout_entry(0), fog::Fog::layout_entry(5)],
             label: None,
         });
 
@@ -208,33 +318,33 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         label: None,
     });
 
-    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("light"),
-        contents: bytemuck::cast_slice(&[DirectionLight {
-            _eye_position: [0.0, 0.0, 0.0],
-            _pad0: 0.0,
-            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
-                0.0f32, 0.0, 1.0,
-            ))
-            .into(),
-            _pad1: 0.0,
-            _directianal_light_color: [0.5, 0.5, 0.5],
-            _pad2: 0.0,
-            _ambient_color: [0.3, 0.3, 0.3],
-        }]),
-        usage: wgpu::BufferUsage::UNIFORM,
+    let mut lights = light::LightSet::new(&device, [0.0, 0.0, 0.0], [0.3, 0.3, 0.3]);
+    lights.push(light::Light::Directional {
+        direction: [0.0, 0.0, 1.0],
+        color: [0.5, 0.5, 0.5],
     });
+    lights.update(&device, &queue);
+    let mut sky_lighting = sky::SkyLighting::new(&lights);
 
-    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &directional_light_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &directional_light_buffer,
-                offset: 0,
-                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
-            }),
-        }],
+    let mut fog = fog::Fog::new_clip_space();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: fog_buffer.as_entire_binding(),
+            },
+        ],
         label: None,
     });
 
@@ -243,13 +353,20 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         bind_group_layouts: &[
             &uniform_bind_group_layout,
             &local_bind_group_layout,
-            &texture_bind_group_layout,
-            &directional_light_bind_group_layout,
+            &ambient_occlusion_bind_group_layout,
+            &light_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let normal_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("normal"),
+        bind_group_layouts: &[&uniform_bind_group_layout, &local_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<loader::Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -300,7 +417,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[swapchain_format.into()],
+            targets: &[hdr::HDR_FORMAT.into()],
         }),
         primitive: wgpu::PrimitiveState {
             cull_mode: Some(wgpu::Face::Front),
@@ -309,8 +426,37 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         depth_stencil: Some(wgpu::DepthStencilState {
             format: texture::Texture::DEPTH_FORMAT,
             depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less, // 1.
-            stencil: wgpu::StencilState::default(),     // 2.
+            depth_compare: wgpu::CompareFunction::LessEqual, // 1.
+            stencil: wgpu::StencilState::default(),          // 2.
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    // Writes the depth buffer and the view space normals that SSAO reads. The lighting pass
+    // then only shades the surfaces left in the depth buffer.
+    let normal_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("normal"),
+        layout: Some(&normal_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_normal",
+            targets: &[ssao::NORMAL_FORMAT.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Front),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState::default(),
@@ -329,11 +475,36 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+    let mut hdr = hdr::HdrPipeline::new(&device, &queue, &sc_desc);
+
+    let mut ssao = ssao::Ssao::new(&device, &sc_desc, &depth_texture.view);
+    let mut ssao_bind_group = ambient_occlusion_bind_group(
+        &device,
+        &ambient_occlusion_bind_group_layout,
+        &white_view,
+        &ambient_occlusion_map.sampler,
+        ssao.occlusion_view(),
+    );
+    let mut baked = false;
+    window.set_title(&ambient_occlusion_title(
+        baked,
+        &ssao,
+        &hdr,
+        &fog,
+        &sky_lighting,
+    ));
+
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
-        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &pipeline_layout,
+            &normal_pipeline_layout,
+        );
 
         *control_flow = ControlFlow::Wait;
         match event {
@@ -347,7 +518,62 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 swap_chain = device.create_swap_chain(&surface, &sc_desc);
                 depth_texture =
                     texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                hdr.resize(&device, &sc_desc);
+                ssao.resize(&device, &sc_desc, &depth_texture.view);
+                ssao_bind_group = ambient_occlusion_bind_group(
+                    &device,
+                    &ambient_occlusion_bind_group_layout,
+                    &white_view,
+                    &ambient_occlusion_map.sampler,
+                    ssao.occlusion_view(),
+                );
             }
+            // B switches between the baked map and SSAO. Up and Down scale the radius, Left and
+            // Right change the intensity and S steps through the sample counts. The other keys are
+            // handled by `HdrPipeline::handle_key`, `Fog::handle_key` and
+            // `SkyLighting::handle_key`.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::B => baked = !baked,
+                    VirtualKeyCode::Up => ssao.radius *= 1.25,
+                    VirtualKeyCode::Down => ssao.radius /= 1.25,
+                    VirtualKeyCode::Right => ssao.intensity += 0.25,
+                    VirtualKeyCode::Left => ssao.intensity = (ssao.intensity - 0.25).max(0.0),
+                    VirtualKeyCode::S => ssao.next_sample_count(),
+                    _ => {
+                        if !hdr.handle_key(keycode)
+                            && !fog.handle_key(keycode)
+                            && !sky_lighting.handle_key(keycode, &mut lights)
+                        {
+                            return;
+                        }
+                    }
+                }
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                lights.update(&device, &queue);
+                window.set_title(&ambient_occlusion_title(
+                    baked,
+                    &ssao,
+                    &hdr,
+                    &fog,
+                    &sky_lighting,
+                ));
+                window.request_redraw();
+            }
+            // Eye adaptation converges over several frames, so keep drawing while it is on.
+            Event::MainEventsCleared if hdr.eye_adaptation => window.request_redraw(),
             Event::RedrawRequested(_) => {
                 let frame = swap_chain
                     .get_current_frame()
@@ -366,10 +592,50 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         );
                     }
 
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("normal"),
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: ssao.normal_view(),
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&normal_pipeline);
+                    rpass.set_bind_group(0, &uniform_bind_group, &[]);
+                    for (i, primitive) in scene.primitives.iter().enumerate() {
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+                if !baked {
+                    ssao.render(&queue, &mut encoder);
+                }
+                {
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &frame.view,
+                            view: hdr.view(),
                             resolve_target: None,
                             ops: wgpu::Operations {
                                 load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
@@ -379,7 +645,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                             view: &depth_texture.view,
                             depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(1.0),
+                                load: wgpu::LoadOp::Load,
                                 store: true,
                             }),
                             stencil_ops: None,
@@ -387,7 +653,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
                     rpass.set_pipeline(&render_pipeline);
                     rpass.set_bind_group(0, &uniform_bind_group, &[]);
-                    rpass.set_bind_group(3, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &light_bind_group, &[]);
                     for (i, primitive) in scene.primitives.iter().enumerate() {
                         rpass.set_bind_group(
                             1,
@@ -395,7 +661,15 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                             &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
                                 as wgpu::DynamicOffset],
                         );
-                        rpass.set_bind_group(2, &ambient_occlusion_bind_group, &[]);
+                        rpass.set_bind_group(
+                            2,
+                            if baked {
+                                &baked_bind_group
+                            } else {
+                                &ssao_bind_group
+                            },
+                            &[],
+                        );
                         rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
                         rpass.set_index_buffer(
                             primitive.index_buffer.slice(..),
@@ -405,6 +679,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     }
                 }
 
+                hdr.render(&queue, &mut encoder, &frame.view);
                 queue.submit(Some(encoder.finish()));
             }
             Event::WindowEvent {
@@ -424,6 +699,303 @@ fn main() {
     pollster::block_on(run(event_loop, window));
 }
 
+// Screen space ambient occlusion. Points in a hemisphere around each pixel are tested against
+// the depth buffer, and the noisy result is blurred without crossing depth edges.
+mod ssao {
+    use std::borrow::Cow;
+
+    use bytemuck::{Pod, Zeroable};
+
+    pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+    // `MAX_SAMPLES` in the shader must not be less than the last entry.
+    pub const SAMPLE_COUNTS: [u32; 4] = [8, 16, 32, 64];
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, Pod, Zeroable)]
+    struct Params {
+        _radius: f32,
+        _intensity: f32,
+        _sample_count: u32,
+        _pad: u32,
+    }
+
+    // The textures that follow the swap chain size.
+    struct Targets {
+        _textures: Vec<wgpu::Texture>,
+        normal_view: wgpu::TextureView,
+        raw_view: wgpu::TextureView,
+        // Blurred horizontally only.
+        blurred_view: wgpu::TextureView,
+        occlusion_view: wgpu::TextureView,
+        ssao_bind_group: wgpu::BindGroup,
+        blur_horizontal_bind_group: wgpu::BindGroup,
+        blur_vertical_bind_group: wgpu::BindGroup,
+    }
+
+    pub struct Ssao {
+        // In view space units, where the teapot is about half a unit wide.
+        pub radius: f32,
+        pub intensity: f32,
+        pub sample_count: u32,
+        params_buffer: wgpu::Buffer,
+        ssao_pipeline: wgpu::RenderPipeline,
+        blur_horizontal_pipeline: wgpu::RenderPipeline,
+        blur_vertical_pipeline: wgpu::RenderPipeline,
+        targets: Targets,
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        entry_point: &str,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[OCCLUSION_FORMAT.into()],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    impl Ssao {
+        pub fn new(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            depth_view: &wgpu::TextureView,
+        ) -> Self {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("ssao"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/ssao.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+            let ssao_pipeline = create_pipeline(device, &shader, "fs_ssao");
+            let blur_horizontal_pipeline = create_pipeline(device, &shader, "fs_blur_horizontal");
+            let blur_vertical_pipeline = create_pipeline(device, &shader, "fs_blur_vertical");
+
+            let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("ssao"),
+                size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let targets = Targets::new(
+                device,
+                sc_desc,
+                depth_view,
+                &params_buffer,
+                [
+                    &ssao_pipeline,
+                    &blur_horizontal_pipeline,
+                    &blur_vertical_pipeline,
+                ],
+            );
+
+            Self {
+                radius: 0.05,
+                intensity: 1.5,
+                sample_count: SAMPLE_COUNTS[1],
+                params_buffer,
+                ssao_pipeline,
+                blur_horizontal_pipeline,
+                blur_vertical_pipeline,
+                targets,
+            }
+        }
+
+        // The targets have to follow the swap chain size, and the depth texture is recreated
+        // along with them.
+        pub fn resize(
+            &mut self,
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            depth_view: &wgpu::TextureView,
+        ) {
+            self.targets = Targets::new(
+                device,
+                sc_desc,
+                depth_view,
+                &self.params_buffer,
+                [
+                    &self.ssao_pipeline,
+                    &self.blur_horizontal_pipeline,
+                    &self.blur_vertical_pipeline,
+                ],
+            );
+        }
+
+        // The color target of the normal pre-pass, which has to run before `render`.
+        pub fn normal_view(&self) -> &wgpu::TextureView {
+            &self.targets.normal_view
+        }
+
+        // The blurred visibility, 1 where nothing is occluded.
+        pub fn occlusion_view(&self) -> &wgpu::TextureView {
+            &self.targets.occlusion_view
+        }
+
+        pub fn next_sample_count(&mut self) {
+            let index = SAMPLE_COUNTS
+                .iter()
+                .position(|&count| count == self.sample_count)
+                .map_or(0, |index| (index + 1) % SAMPLE_COUNTS.len());
+            self.sample_count = SAMPLE_COUNTS[index];
+        }
+
+        pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+            let params = Params {
+                _radius: self.radius,
+                _intensity: self.intensity,
+                _sample_count: self.sample_count,
+                _pad: 0,
+            };
+            queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+
+            let passes = [
+                (
+                    &self.ssao_pipeline,
+                    &self.targets.ssao_bind_group,
+                    &self.targets.raw_view,
+                ),
+                (
+                    &self.blur_horizontal_pipeline,
+                    &self.targets.blur_horizontal_bind_group,
+                    &self.targets.blurred_view,
+                ),
+                (
+                    &self.blur_vertical_pipeline,
+                    &self.targets.blur_vertical_bind_group,
+                    &self.targets.occlusion_view,
+                ),
+            ];
+            for (pipeline, bind_group, target) in passes.iter() {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("ssao"),
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(0, bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+        }
+    }
+
+    impl Targets {
+        fn new(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            depth_view: &wgpu::TextureView,
+            params_buffer: &wgpu::Buffer,
+            pipelines: [&wgpu::RenderPipeline; 3],
+        ) -> Self {
+            let [ssao_pipeline, blur_horizontal_pipeline, blur_vertical_pipeline] = pipelines;
+            let (normal, normal_view) = create_target(device, sc_desc, NORMAL_FORMAT, "normal");
+            let (raw, raw_view) = create_target(device, sc_desc, OCCLUSION_FORMAT, "ssao");
+            let (blurred, blurred_view) =
+                create_target(device, sc_desc, OCCLUSION_FORMAT, "ssao blurred");
+            let (occlusion, occlusion_view) =
+                create_target(device, sc_desc, OCCLUSION_FORMAT, "ambient occlusion");
+
+            // Each pipeline has its own automatic layout with only the bindings it uses.
+            let ssao_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &ssao_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&normal_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("ssao_bind_group"),
+            });
+            let blur_bind_group = |pipeline: &wgpu::RenderPipeline, source| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pipeline.get_bind_group_layout(0),
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(depth_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(source),
+                        },
+                    ],
+                    label: Some("ssao_blur_bind_group"),
+                })
+            };
+            let blur_horizontal_bind_group = blur_bind_group(blur_horizontal_pipeline, &raw_view);
+            let blur_vertical_bind_group = blur_bind_group(blur_vertical_pipeline, &blurred_view);
+
+            Self {
+                _textures: vec![normal, raw, blurred, occlusion],
+                normal_view,
+                raw_view,
+                blurred_view,
+                occlusion_view,
+                ssao_bind_group,
+                blur_horizontal_bind_group,
+                blur_vertical_bind_group,
+            }
+        }
+    }
+}
+
 mod loader {
     use super::*;
     use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
@@ -481,8 +1053,14 @@ mod loader {
                         }
                         .unwrap();
 
-                        let diffuse_texture =
-                            texture::Texture::from_image(device, queue, &image, None).unwrap();
+                        let diffuse_texture = texture::Texture::from_image(
+                            device,
+                            queue,
+                            &image,
+                            None,
+                            texture::TextureRole::Color,
+                        )
+                        .unwrap();
 
                         let diffuse_bind_group =
                             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -615,7 +1193,10 @@ mod loader {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
@@ -626,6 +1207,28 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+        // Linear data such as specular or ambient occlusion masks. Only the channels the source
+        // has are kept, so grayscale masks take a quarter of the memory of RGBA8.
+        Data,
+    }
+
+    impl TextureRole {
+        pub fn format(self, color: image::ColorType) -> wgpu::TextureFormat {
+            match (self, color) {
+                (TextureRole::Color, _) => wgpu::TextureFormat::Rgba8UnormSrgb,
+                (TextureRole::Data, image::ColorType::L8)
+                | (TextureRole::Data, image::ColorType::L16) => wgpu::TextureFormat::R8Unorm,
+                (TextureRole::Data, image::ColorType::La8)
+                | (TextureRole::Data, image::ColorType::La16) => wgpu::TextureFormat::Rg8Unorm,
+                (TextureRole::Data, _) => wgpu::TextureFormat::Rgba8Unorm,
+            }
+        }
+    }
+
     impl Texture {
         pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -634,9 +1237,10 @@ mod texture {
             queue: &wgpu::Queue,
             bytes: &[u8],
             label: &str,
+            role: TextureRole,
         ) -> Result<Self> {
             let img = image::load_from_memory(bytes)?;
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), role)
         }
 
         pub fn create_depth_texture(
@@ -689,8 +1293,8 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
-            let rgba = img.to_rgba8().to_vec();
             let dimensions = img.dimensions();
 
             let size = wgpu::Extent3d {
@@ -698,14 +1302,23 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format(img.color());
+            let (pixels, bytes_per_pixel) = match format {
+                wgpu::TextureFormat::R8Unorm => (img.to_luma8().into_raw(), 1),
+                wgpu::TextureFormat::Rg8Unorm => (img.to_luma_alpha8().into_raw(), 2),
+                _ => (img.to_rgba8().into_raw(), 4),
+            };
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -714,23 +1327,29 @@ mod texture {
                     mip_level: 0,
                     origin: wgpu::Origin3d::ZERO,
                 },
-                &rgba,
+                &pixels,
                 wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    bytes_per_row: NonZeroU32::new(bytes_per_pixel * dimensions.0),
                     rows_per_image: NonZeroU32::new(dimensions.1),
                 },
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -740,5 +1359,115 @@ mod texture {
                 sampler,
             })
         }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
     }
 }
@@ -3,27 +3,191 @@ use cgmath::{Matrix4, Quaternion};
 use std::{borrow::Cow, mem::size_of};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
+#[path = "common/fog.rs"]
+mod fog;
+#[path = "common/light.rs"]
+mod light;
+#[path = "common/sky.rs"]
+mod sky;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 struct Matrix {
     _matrix: [[f32; 4]; 4],
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Distribution {
+    Beckmann,
+    Ggx,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Geometry {
+    CookTorrance,
+    SmithSchlickGgx,
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct DirectionLight {
-    _eye_position: [f32; 3],
-    _pad0: f32,
-    _directional_light_direction: [f32; 3],
-    _pad1: f32,
-    _directianal_light_color: [f32; 3],
-    _pad2: f32,
-    _ambient_color: [f32; 3],
+struct MaterialUniform {
+    _base_color: [f32; 3],
+    _roughness: f32,
+    _f0: [f32; 3],
+    _metallic: f32,
+    _distribution: u32,
+    _geometry: u32,
+    _pad: [u32; 2],
+    _emissive: [f32; 3],
+    _emissive_strength: f32,
+}
+
+// Metallic-roughness parameters. `f0` is the reflectance at normal incidence of the dielectric
+// part, metals reflect their base color instead.
+#[derive(Debug, Clone, Copy)]
+struct Material {
+    name: &'static str,
+    base_color: [f32; 3],
+    roughness: f32,
+    metallic: f32,
+    f0: [f32; 3],
+    distribution: Distribution,
+    geometry: Geometry,
+    // Multiplies the emissive texture. The strength is kept apart so that the color stays
+    // readable, emission is not limited to 1 in the HDR target.
+    emissive: [f32; 3],
+    emissive_strength: f32,
+}
+
+impl Material {
+    fn uniform(&self) -> MaterialUniform {
+        MaterialUniform {
+            _base_color: self.base_color,
+            _roughness: self.roughness,
+            _f0: self.f0,
+            _metallic: self.metallic,
+            _distribution: self.distribution as u32,
+            _geometry: self.geometry as u32,
+            _pad: [0; 2],
+            _emissive: self.emissive,
+            _emissive_strength: self.emissive_strength,
+        }
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "sample_07_03: {}, roughness {:.2}, metallic {:.2}, {:?} D, {:?} G, emissive {}",
+            self.name,
+            self.roughness,
+            self.metallic,
+            self.distribution,
+            self.geometry,
+            self.emissive_strength
+        )
+    }
+}
+
+// Base colors of metals are their measured F0.
+const MATERIAL_PRESETS: [Material; 5] = [
+    Material {
+        name: "plastic",
+        base_color: [0.8, 0.1, 0.1],
+        roughness: 0.5,
+        metallic: 0.0,
+        f0: [0.04, 0.04, 0.04],
+        distribution: Distribution::Ggx,
+        geometry: Geometry::SmithSchlickGgx,
+        emissive: [0.0, 0.0, 0.0],
+        emissive_strength: 0.0,
+    },
+    Material {
+        name: "gold",
+        base_color: [1.0, 0.766, 0.336],
+        roughness: 0.3,
+        metallic: 1.0,
+        f0: [0.04, 0.04, 0.04],
+        distribution: Distribution::Ggx,
+        geometry: Geometry::SmithSchlickGgx,
+        emissive: [0.0, 0.0, 0.0],
+        emissive_strength: 0.0,
+    },
+    Material {
+        name: "copper",
+        base_color: [0.955, 0.638, 0.538],
+        roughness: 0.4,
+        metallic: 1.0,
+        f0: [0.04, 0.04, 0.04],
+        distribution: Distribution::Ggx,
+        geometry: Geometry::SmithSchlickGgx,
+        emissive: [0.0, 0.0, 0.0],
+        emissive_strength: 0.0,
+    },
+    Material {
+        name: "iron",
+        base_color: [0.56, 0.57, 0.58],
+        roughness: 0.6,
+        metallic: 1.0,
+        f0: [0.04, 0.04, 0.04],
+        distribution: Distribution::Ggx,
+        geometry: Geometry::SmithSchlickGgx,
+        emissive: [0.0, 0.0, 0.0],
+        emissive_strength: 0.0,
+    },
+    Material {
+        name: "neon",
+        base_color: [0.05, 0.05, 0.05],
+        roughness: 0.4,
+        metallic: 0.0,
+        f0: [0.04, 0.04, 0.04],
+        distribution: Distribution::Ggx,
+        geometry: Geometry::SmithSchlickGgx,
+        emissive: [0.2, 0.8, 1.0],
+        emissive_strength: 4.0,
+    },
+];
+
+// E steps through these.
+const EMISSIVE_STRENGTHS: [f32; 4] = [0.0, 1.0, 4.0, 16.0];
+
+fn title(
+    material: &Material,
+    bloom: &bloom::Bloom,
+    fog: &fog::Fog,
+    sky_lighting: &sky::SkyLighting,
+) -> String {
+    format!(
+        "{}, {}, {} (F, H, -/=), {} (L, Comma/Period)",
+        material.title(),
+        bloom.title(),
+        fog.title(),
+        sky_lighting.title()
+    )
+}
+
+// Glowing bands around the teapot, multiplied by the material's emissive color.
+fn emissive_pattern() -> image::DynamicImage {
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(256, 256, |_, y| {
+        if y % 64 < 8 {
+            image::Rgba([255, 255, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    }))
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
 }
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
@@ -78,23 +242,100 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: Some("texture_bind_group_layout"),
         });
 
-    let ambient_occlusion = include_bytes!("../assets/298186.png");
-    let ambient_occlusion_map =
-        texture::Texture::from_bytes(&device, &queue, ambient_occlusion, "Specular Map").unwrap();
+    // The group 2 textures plus the material parameters.
+    let material_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<MaterialUniform>() as wgpu::BufferAddress
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("material_bind_group_layout"),
+        });
+
+    let mut current_preset = 0;
+    let mut material = MATERIAL_PRESETS[current_preset];
+
+    let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("material"),
+        contents: bytemuck::bytes_of(&material.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
 
-    let ambient_occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &texture_bind_group_layout,
+    // Occlusion, roughness and metallic packed into R, G and B by `pack_orm`.
+    let orm = include_bytes!("../assets/teapot_orm.png");
+    let orm_map =
+        texture::Texture::from_bytes(&device, &queue, orm, "ORM Map", texture::TextureRole::Data)
+            .unwrap();
+
+    let emissive_map = texture::Texture::from_image(
+        &device,
+        &queue,
+        &emissive_pattern(),
+        Some("Emissive Map"),
+        texture::TextureRole::Color,
+    )
+    .unwrap();
+
+    let material_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &material_bind_group_layout,
         entries: &[
             wgpu::BindGroupEntry {
                 binding: 0,
-                resource: wgpu::BindingResource::TextureView(&ambient_occlusion_map.view),
+                resource: wgpu::BindingResource::TextureView(&orm_map.view),
             },
             wgpu::BindGroupEntry {
                 binding: 1,
-                resource: wgpu::BindingResource::Sampler(&ambient_occlusion_map.sampler),
+                resource: wgpu::BindingResource::Sampler(&orm_map.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: material_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&emissive_map.view),
             },
         ],
-        label: Some("diffuse_bind_group"),
+        label: Some("material_bind_group"),
     });
 
     let teapot = include_bytes!("../assets/teapot.glb");
@@ -113,7 +354,11 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     // Load the shaders from disk
     let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
         label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/07_03.wgsl"))),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/07_03.wgsl")
+        ))),
         flags: wgpu::ShaderFlags::all(),
     });
 
@@ -171,18 +416,45 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             label: None,
         });
 
-    let directional_light_bind_group_layout =
+    let cube_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+
+    // The environment lighting shares the group with the light, all four groups are taken.
+    let light_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                light::LightSet::layout_entry(0),
+                cube_entry(1),
+                cube_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                fog::Fog::layout_entry(5),
+            ],
             label: None,
         });
 
@@ -208,33 +480,54 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         label: None,
     });
 
-    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("light"),
-        contents: bytemuck::cast_slice(&[DirectionLight {
-            _eye_position: [0.0, 0.0, 0.0],
-            _pad0: 0.0,
-            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
-                1.0f32, -1.0, 1.0,
-            ))
-            .into(),
-            _pad1: 0.0,
-            _directianal_light_color: [0.5, 0.5, 0.5],
-            _pad2: 0.0,
-            _ambient_color: [0.3, 0.3, 0.3],
-        }]),
-        usage: wgpu::BufferUsage::UNIFORM,
+    // The ambient color scales the environment lighting.
+    let mut lights = light::LightSet::new(&device, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+    lights.push(light::Light::Directional {
+        direction: [1.0, -1.0, 1.0],
+        color: [0.5, 0.5, 0.5],
     });
+    lights.update(&device, &queue);
+    let mut sky_lighting = sky::SkyLighting::new(&lights);
 
-    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &directional_light_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &directional_light_buffer,
-                offset: 0,
-                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
-            }),
-        }],
+    let mut fog = fog::Fog::new_clip_space();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let environment =
+        ibl::Environment::from_radiance(&device, &queue, include_bytes!("../assets/sky.hdr"))
+            .unwrap();
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&environment.irradiance.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&environment.prefiltered.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&environment.brdf_lut.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Sampler(&environment.prefiltered.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: fog_buffer.as_entire_binding(),
+            },
+        ],
         label: None,
     });
 
@@ -243,13 +536,14 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         bind_group_layouts: &[
             &uniform_bind_group_layout,
             &local_bind_group_layout,
-            &texture_bind_group_layout,
-            &directional_light_bind_group_layout,
+            &material_bind_group_layout,
+            &light_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
 
-    let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
 
     let vertex_size = std::mem::size_of::<loader::Vertex>();
     let vertex_buffers = [wgpu::VertexBufferLayout {
@@ -300,7 +594,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[swapchain_format.into()],
+            targets: &[bloom::HDR_FORMAT.into()],
         }),
         primitive: wgpu::PrimitiveState {
             cull_mode: Some(wgpu::Face::Front),
@@ -329,6 +623,9 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
+    let mut bloom = bloom::Bloom::new(&device, &sc_desc);
+    window.set_title(&title(&material, &bloom, &fog, &sky_lighting));
+
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
@@ -347,6 +644,69 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 swap_chain = device.create_swap_chain(&surface, &sc_desc);
                 depth_texture =
                     texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                bloom.resize(&device, &sc_desc);
+            }
+            // The fog and sky keys are handled by `Fog::handle_key` and `SkyLighting::handle_key`.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::M => {
+                        current_preset = (current_preset + 1) % MATERIAL_PRESETS.len();
+                        material = MATERIAL_PRESETS[current_preset];
+                    }
+                    VirtualKeyCode::Up => material.roughness = (material.roughness + 0.05).min(1.0),
+                    VirtualKeyCode::Down => {
+                        material.roughness = (material.roughness - 0.05).max(0.0)
+                    }
+                    VirtualKeyCode::Right => material.metallic = (material.metallic + 0.1).min(1.0),
+                    VirtualKeyCode::Left => material.metallic = (material.metallic - 0.1).max(0.0),
+                    VirtualKeyCode::D => {
+                        material.distribution = match material.distribution {
+                            Distribution::Beckmann => Distribution::Ggx,
+                            Distribution::Ggx => Distribution::Beckmann,
+                        }
+                    }
+                    VirtualKeyCode::G => {
+                        material.geometry = match material.geometry {
+                            Geometry::CookTorrance => Geometry::SmithSchlickGgx,
+                            Geometry::SmithSchlickGgx => Geometry::CookTorrance,
+                        }
+                    }
+                    VirtualKeyCode::E => {
+                        let index = EMISSIVE_STRENGTHS
+                            .iter()
+                            .position(|&strength| strength == material.emissive_strength)
+                            .map_or(0, |index| (index + 1) % EMISSIVE_STRENGTHS.len());
+                        material.emissive_strength = EMISSIVE_STRENGTHS[index];
+                    }
+                    VirtualKeyCode::B => bloom.enabled = !bloom.enabled,
+                    VirtualKeyCode::T => bloom.next_threshold(),
+                    VirtualKeyCode::PageUp => bloom.intensity = (bloom.intensity + 0.05).min(1.0),
+                    VirtualKeyCode::PageDown => bloom.intensity = (bloom.intensity - 0.05).max(0.0),
+                    _ => {
+                        if !fog.handle_key(keycode)
+                            && !sky_lighting.handle_key(keycode, &mut lights)
+                        {
+                            return;
+                        }
+                    }
+                }
+                queue.write_buffer(&material_buffer, 0, bytemuck::bytes_of(&material.uniform()));
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                lights.update(&device, &queue);
+                window.set_title(&title(&material, &bloom, &fog, &sky_lighting));
+                window.request_redraw();
             }
             Event::RedrawRequested(_) => {
                 let frame = swap_chain
@@ -369,7 +729,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[wgpu::RenderPassColorAttachment {
-                            view: &frame.view,
+                            view: bloom.view(),
                             resolve_target: None,
                             ops: wgpu::Operations {
                                 load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
@@ -387,7 +747,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
                     rpass.set_pipeline(&render_pipeline);
                     rpass.set_bind_group(0, &uniform_bind_group, &[]);
-                    rpass.set_bind_group(3, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &light_bind_group, &[]);
                     for (i, primitive) in scene.primitives.iter().enumerate() {
                         rpass.set_bind_group(
                             1,
@@ -395,7 +755,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                             &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
                                 as wgpu::DynamicOffset],
                         );
-                        rpass.set_bind_group(2, &ambient_occlusion_bind_group, &[]);
+                        rpass.set_bind_group(2, &material_bind_group, &[]);
                         rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
                         rpass.set_index_buffer(
                             primitive.index_buffer.slice(..),
@@ -404,6 +764,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
                     }
                 }
+                bloom.render(&queue, &mut encoder, &frame.view);
 
                 queue.submit(Some(encoder.finish()));
             }
@@ -481,8 +842,14 @@ mod loader {
                         }
                         .unwrap();
 
-                        let diffuse_texture =
-                            texture::Texture::from_image(device, queue, &image, None).unwrap();
+                        let diffuse_texture = texture::Texture::from_image(
+                            device,
+                            queue,
+                            &image,
+                            None,
+                            texture::TextureRole::Color,
+                        )
+                        .unwrap();
 
                         let diffuse_bind_group =
                             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -615,10 +982,15 @@ mod loader {
 }
 
 mod texture {
-    use std::num::NonZeroU32;
+    use std::{
+        borrow::Cow,
+        io::{BufReader, Cursor},
+        num::{NonZeroU32, NonZeroU8},
+    };
 
     use anyhow::*;
     use image::GenericImageView;
+    use wgpu::util::DeviceExt;
 
     pub struct Texture {
         pub texture: wgpu::Texture,
@@ -626,6 +998,28 @@ mod texture {
         pub sampler: wgpu::Sampler,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+        // Linear data such as specular or ambient occlusion masks. Only the channels the source
+        // has are kept, so grayscale masks take a quarter of the memory of RGBA8.
+        Data,
+    }
+
+    impl TextureRole {
+        pub fn format(self, color: image::ColorType) -> wgpu::TextureFormat {
+            match (self, color) {
+                (TextureRole::Color, _) => wgpu::TextureFormat::Rgba8UnormSrgb,
+                (TextureRole::Data, image::ColorType::L8)
+                | (TextureRole::Data, image::ColorType::L16) => wgpu::TextureFormat::R8Unorm,
+                (TextureRole::Data, image::ColorType::La8)
+                | (TextureRole::Data, image::ColorType::La16) => wgpu::TextureFormat::Rg8Unorm,
+                (TextureRole::Data, _) => wgpu::TextureFormat::Rgba8Unorm,
+            }
+        }
+    }
+
     impl Texture {
         pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -634,9 +1028,10 @@ mod texture {
             queue: &wgpu::Queue,
             bytes: &[u8],
             label: &str,
+            role: TextureRole,
         ) -> Result<Self> {
             let img = image::load_from_memory(bytes)?;
-            Self::from_image(device, queue, &img, Some(label))
+            Self::from_image(device, queue, &img, Some(label), role)
         }
 
         pub fn create_depth_texture(
@@ -689,8 +1084,8 @@ mod texture {
             queue: &wgpu::Queue,
             img: &image::DynamicImage,
             label: Option<&str>,
+            role: TextureRole,
         ) -> Result<Self> {
-            let rgba = img.to_rgba8().to_vec();
             let dimensions = img.dimensions();
 
             let size = wgpu::Extent3d {
@@ -698,14 +1093,23 @@ mod texture {
                 height: dimensions.1,
                 depth_or_array_layers: 1,
             };
+            let format = role.format(img.color());
+            let (pixels, bytes_per_pixel) = match format {
+                wgpu::TextureFormat::R8Unorm => (img.to_luma8().into_raw(), 1),
+                wgpu::TextureFormat::Rg8Unorm => (img.to_luma_alpha8().into_raw(), 2),
+                _ => (img.to_rgba8().into_raw(), 4),
+            };
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
             });
 
             queue.write_texture(
@@ -714,23 +1118,29 @@ mod texture {
                     mip_level: 0,
                     origin: wgpu::Origin3d::ZERO,
                 },
-                &rgba,
+                &pixels,
                 wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    bytes_per_row: NonZeroU32::new(bytes_per_pixel * dimensions.0),
                     rows_per_image: NonZeroU32::new(dimensions.1),
                 },
                 size,
             );
 
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
             let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
             let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
                 mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
                 ..Default::default()
             });
 
@@ -740,5 +1150,1028 @@ mod texture {
                 sampler,
             })
         }
+
+        // Render mip level 0 of the six faces from an equirectangular Radiance HDR image. The
+        // other levels are left for the caller to fill, the result keeps the full range in a
+        // half float cube map.
+        pub fn create_cube_from_equirectangular(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            face_size: u32,
+            mip_level_count: u32,
+            label: &str,
+        ) -> Result<Self> {
+            let equirect = Self::from_radiance(device, queue, bytes, label)?;
+            let format = wgpu::TextureFormat::Rgba16Float;
+
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: face_size,
+                    height: face_size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("equirect_to_cube"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/equirect_to_cube.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("equirect_to_cube"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for face in 0..6 {
+                let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("face index"),
+                    contents: bytemuck::cast_slice(&[face, 0, 0, 0]),
+                    usage: wgpu::BufferUsage::UNIFORM,
+                });
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&equirect.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&equirect.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: face_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let face_view = Self::face_view(&texture, face, 0);
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &face_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+
+            Ok(Self::from_cube_texture(device, texture))
+        }
+
+        // A cube view of every mip level, sampled with trilinear filtering.
+        pub fn from_cube_texture(device: &wgpu::Device, texture: wgpu::Texture) -> Self {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+            }
+        }
+
+        // A 2D view of a single face and mip level, for rendering into.
+        pub fn face_view(texture: &wgpu::Texture, face: u32, mip_level: u32) -> wgpu::TextureView {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("cube face"),
+                format: None,
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                aspect: wgpu::TextureAspect::All,
+                base_mip_level: mip_level,
+                mip_level_count: NonZeroU32::new(1),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+            })
+        }
+
+        // RGBE only has 8 bits of mantissa, so half floats lose nothing.
+        fn from_radiance(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+        ) -> Result<Self> {
+            let decoder = image::codecs::hdr::HdrDecoder::new(BufReader::new(Cursor::new(bytes)))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .flat_map(|image::Rgb([r, g, b])| {
+                    [r, g, b, 1.0]
+                        .iter()
+                        .map(|&c| half::f16::from_f32(c).to_bits())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<u16>>();
+
+            let texture = device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: metadata.width,
+                        height: metadata.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                },
+                bytemuck::cast_slice(&pixels),
+            );
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Wrap horizontally across the longitude seam.
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Ok(Self {
+                texture,
+                view,
+                sampler,
+            })
+        }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
+
+// Image based lighting from an HDR environment, precomputed on the GPU with the split sum
+// approximation: a diffuse irradiance cube map, a specular cube map prefiltered with a wider
+// GGX lobe in each mip level, and a BRDF lookup table.
+mod ibl {
+    use std::{borrow::Cow, num::NonZeroU32};
+
+    use super::texture::Texture;
+    use anyhow::*;
+    use bytemuck::{Pod, Zeroable};
+    use wgpu::util::DeviceExt;
+
+    const ENVIRONMENT_SIZE: u32 = 256;
+    const IRRADIANCE_SIZE: u32 = 32;
+    const PREFILTERED_SIZE: u32 = 128;
+    // Roughness 0 in the top level up to 1 in the last. The PBR shaders map roughness to the
+    // mip level with the same count.
+    pub const PREFILTERED_MIP_LEVELS: u32 = 5;
+    const BRDF_LUT_SIZE: u32 = 256;
+
+    const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+    #[repr(C)]
+    #[derive(Debug, Default, Copy, Clone, Pod, Zeroable)]
+    struct Filter {
+        _face: u32,
+        _roughness: f32,
+        _source_size: f32,
+        _pad: f32,
+    }
+
+    pub struct Environment {
+        pub irradiance: Texture,
+        pub prefiltered: Texture,
+        pub brdf_lut: Texture,
+    }
+
+    impl Environment {
+        pub fn from_radiance(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+        ) -> Result<Self> {
+            let mip_level_count = 32 - ENVIRONMENT_SIZE.leading_zeros();
+            let environment = Texture::create_cube_from_equirectangular(
+                device,
+                queue,
+                bytes,
+                ENVIRONMENT_SIZE,
+                mip_level_count,
+                "environment",
+            )?;
+
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("ibl"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/ibl.wgsl"))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+            let downsample = create_pipeline(device, &shader, "fs_downsample", CUBE_FORMAT);
+            let irradiance_pipeline =
+                create_pipeline(device, &shader, "fs_irradiance", CUBE_FORMAT);
+            let prefilter = create_pipeline(device, &shader, "fs_prefilter", CUBE_FORMAT);
+            let brdf_lut_pipeline =
+                create_pipeline(device, &shader, "fs_brdf_lut", BRDF_LUT_FORMAT);
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            // The filters read lower resolution levels for wide lobes, so the environment needs
+            // a full mip chain first.
+            for mip_level in 1..mip_level_count {
+                let source = environment
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("environment mip"),
+                        dimension: Some(wgpu::TextureViewDimension::Cube),
+                        base_mip_level: mip_level - 1,
+                        mip_level_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    });
+                render_faces(
+                    device,
+                    &mut encoder,
+                    &downsample,
+                    &source,
+                    &environment.sampler,
+                    &environment.texture,
+                    mip_level,
+                    Filter::default(),
+                );
+            }
+
+            let source = Filter {
+                _source_size: ENVIRONMENT_SIZE as f32,
+                ..Default::default()
+            };
+
+            let irradiance = create_cube(device, IRRADIANCE_SIZE, 1, "irradiance");
+            render_faces(
+                device,
+                &mut encoder,
+                &irradiance_pipeline,
+                &environment.view,
+                &environment.sampler,
+                &irradiance,
+                0,
+                source,
+            );
+
+            let prefiltered = create_cube(
+                device,
+                PREFILTERED_SIZE,
+                PREFILTERED_MIP_LEVELS,
+                "prefiltered",
+            );
+            for mip_level in 0..PREFILTERED_MIP_LEVELS {
+                render_faces(
+                    device,
+                    &mut encoder,
+                    &prefilter,
+                    &environment.view,
+                    &environment.sampler,
+                    &prefiltered,
+                    mip_level,
+                    Filter {
+                        _roughness: mip_level as f32 / (PREFILTERED_MIP_LEVELS - 1) as f32,
+                        ..source
+                    },
+                );
+            }
+
+            let brdf_lut = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("brdf_lut"),
+                size: wgpu::Extent3d {
+                    width: BRDF_LUT_SIZE,
+                    height: BRDF_LUT_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: BRDF_LUT_FORMAT,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+            let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+            {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("brdf_lut"),
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &brdf_lut_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&brdf_lut_pipeline);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+
+            let brdf_lut_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Ok(Self {
+                irradiance: Texture::from_cube_texture(device, irradiance),
+                prefiltered: Texture::from_cube_texture(device, prefiltered),
+                brdf_lut: Texture {
+                    texture: brdf_lut,
+                    view: brdf_lut_view,
+                    sampler: brdf_lut_sampler,
+                },
+            })
+        }
+    }
+
+    fn create_cube(
+        device: &wgpu::Device,
+        size: u32,
+        mip_level_count: u32,
+        label: &str,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CUBE_FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        entry_point: &str,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    // Run `pipeline` over all six faces of one mip level of `target`.
+    #[allow(clippy::too_many_arguments)]
+    fn render_faces(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        source: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        target: &wgpu::Texture,
+        mip_level: u32,
+        filter: Filter,
+    ) {
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        for face in 0..6 {
+            let filter_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("filter"),
+                contents: bytemuck::bytes_of(&Filter {
+                    _face: face,
+                    ..filter
+                }),
+                usage: wgpu::BufferUsage::UNIFORM,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: filter_buffer.as_entire_binding(),
+                    },
+                ],
+                label: None,
+            });
+
+            let face_view = Texture::face_view(target, face, mip_level);
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &face_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..4, 0..1);
+        }
+    }
+}
+
+// Bloom on an HDR target. The parts of the frame above a threshold are blurred by going down
+// a mip chain and back up again, then added to the frame before tone mapping.
+mod bloom {
+    use std::{borrow::Cow, num::NonZeroU32};
+
+    use bytemuck::{Pod, Zeroable};
+
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    // The chain starts at half the window size. Small windows get fewer levels.
+    const MIP_LEVELS: u32 = 6;
+    pub const THRESHOLDS: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, Pod, Zeroable)]
+    struct Params {
+        _threshold: f32,
+        _knee: f32,
+        _intensity: f32,
+        _pad: f32,
+    }
+
+    // The textures that follow the swap chain size.
+    struct Targets {
+        _textures: Vec<wgpu::Texture>,
+        hdr_view: wgpu::TextureView,
+        mip_views: Vec<wgpu::TextureView>,
+        threshold_bind_group: wgpu::BindGroup,
+        // Level `i` into level `i + 1`.
+        downsample_bind_groups: Vec<wgpu::BindGroup>,
+        // Level `i + 1` into level `i`.
+        upsample_bind_groups: Vec<wgpu::BindGroup>,
+        composite_bind_group: wgpu::BindGroup,
+    }
+
+    impl Targets {
+        fn new(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            sampler: &wgpu::Sampler,
+            params_buffer: &wgpu::Buffer,
+            pipelines: [&wgpu::RenderPipeline; 4],
+        ) -> Self {
+            let [threshold_pipeline, downsample_pipeline, upsample_pipeline, composite_pipeline] =
+                pipelines;
+
+            let hdr = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("hdr target"),
+                size: wgpu::Extent3d {
+                    width: sc_desc.width,
+                    height: sc_desc.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+            let hdr_view = hdr.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let width = (sc_desc.width / 2).max(1);
+            let height = (sc_desc.height / 2).max(1);
+            let mip_level_count = MIP_LEVELS.min(32 - width.min(height).leading_zeros());
+            let chain = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("bloom"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+            let mip_views = (0..mip_level_count)
+                .map(|mip| {
+                    chain.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("bloom mip"),
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let source_bind_group =
+                |pipeline: &wgpu::RenderPipeline, source: &wgpu::TextureView, label: &str| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: &pipeline.get_bind_group_layout(0),
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(source),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(sampler),
+                            },
+                        ],
+                        label: Some(label),
+                    })
+                };
+            let downsample_bind_groups = mip_views[..mip_views.len() - 1]
+                .iter()
+                .map(|source| source_bind_group(downsample_pipeline, source, "bloom downsample"))
+                .collect();
+            let upsample_bind_groups = mip_views[1..]
+                .iter()
+                .map(|source| source_bind_group(upsample_pipeline, source, "bloom upsample"))
+                .collect();
+
+            let threshold_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &threshold_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&hdr_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("bloom threshold"),
+            });
+            let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &composite_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&hdr_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&mip_views[0]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("bloom composite"),
+            });
+
+            Self {
+                _textures: vec![hdr, chain],
+                hdr_view,
+                mip_views,
+                threshold_bind_group,
+                downsample_bind_groups,
+                upsample_bind_groups,
+                composite_bind_group,
+            }
+        }
+    }
+
+    pub struct Bloom {
+        pub enabled: bool,
+        pub threshold: f32,
+        pub intensity: f32,
+        sampler: wgpu::Sampler,
+        params_buffer: wgpu::Buffer,
+        threshold_pipeline: wgpu::RenderPipeline,
+        downsample_pipeline: wgpu::RenderPipeline,
+        upsample_pipeline: wgpu::RenderPipeline,
+        composite_pipeline: wgpu::RenderPipeline,
+        targets: Targets,
+    }
+
+    impl Bloom {
+        pub fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) -> Self {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("bloom"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/bloom.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let threshold_pipeline = create_pipeline(device, &shader, "fs_threshold", HDR_FORMAT);
+            let downsample_pipeline = create_pipeline(device, &shader, "fs_downsample", HDR_FORMAT);
+            // Each level keeps what was downsampled into it and gets the blurred smaller
+            // level added on top.
+            let upsample_pipeline = create_pipeline(
+                device,
+                &shader,
+                "fs_upsample",
+                wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent::REPLACE,
+                    }),
+                    write_mask: wgpu::ColorWrite::ALL,
+                },
+            );
+            let composite_pipeline =
+                create_pipeline(device, &shader, "fs_composite", sc_desc.format);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("bloom"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("bloom"),
+                size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let targets = Targets::new(
+                device,
+                sc_desc,
+                &sampler,
+                &params_buffer,
+                [
+                    &threshold_pipeline,
+                    &downsample_pipeline,
+                    &upsample_pipeline,
+                    &composite_pipeline,
+                ],
+            );
+
+            Self {
+                enabled: true,
+                threshold: THRESHOLDS[1],
+                intensity: 0.3,
+                sampler,
+                params_buffer,
+                threshold_pipeline,
+                downsample_pipeline,
+                upsample_pipeline,
+                composite_pipeline,
+                targets,
+            }
+        }
+
+        // The view the scene renders into.
+        pub fn view(&self) -> &wgpu::TextureView {
+            &self.targets.hdr_view
+        }
+
+        pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+            self.targets = Targets::new(
+                device,
+                sc_desc,
+                &self.sampler,
+                &self.params_buffer,
+                [
+                    &self.threshold_pipeline,
+                    &self.downsample_pipeline,
+                    &self.upsample_pipeline,
+                    &self.composite_pipeline,
+                ],
+            );
+        }
+
+        pub fn next_threshold(&mut self) {
+            let index = THRESHOLDS
+                .iter()
+                .position(|&threshold| threshold == self.threshold)
+                .map_or(0, |index| (index + 1) % THRESHOLDS.len());
+            self.threshold = THRESHOLDS[index];
+        }
+
+        pub fn title(&self) -> String {
+            if self.enabled {
+                format!(
+                    "bloom threshold {:.1}, intensity {:.2}",
+                    self.threshold, self.intensity
+                )
+            } else {
+                "bloom off".to_string()
+            }
+        }
+
+        // Blurs the bright parts of the HDR target and composites them into `output`.
+        pub fn render(
+            &self,
+            queue: &wgpu::Queue,
+            encoder: &mut wgpu::CommandEncoder,
+            output: &wgpu::TextureView,
+        ) {
+            queue.write_buffer(
+                &self.params_buffer,
+                0,
+                bytemuck::bytes_of(&Params {
+                    _threshold: self.threshold,
+                    _knee: self.threshold * 0.25,
+                    _intensity: if self.enabled { self.intensity } else { 0.0 },
+                    _pad: 0.0,
+                }),
+            );
+
+            let targets = &self.targets;
+            if self.enabled {
+                fullscreen_pass(
+                    encoder,
+                    &targets.mip_views[0],
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    &self.threshold_pipeline,
+                    &targets.threshold_bind_group,
+                );
+                for (target, bind_group) in targets.mip_views[1..]
+                    .iter()
+                    .zip(targets.downsample_bind_groups.iter())
+                {
+                    fullscreen_pass(
+                        encoder,
+                        target,
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        &self.downsample_pipeline,
+                        bind_group,
+                    );
+                }
+                for (target, bind_group) in targets.mip_views[..targets.mip_views.len() - 1]
+                    .iter()
+                    .zip(targets.upsample_bind_groups.iter())
+                    .rev()
+                {
+                    fullscreen_pass(
+                        encoder,
+                        target,
+                        wgpu::LoadOp::Load,
+                        &self.upsample_pipeline,
+                        bind_group,
+                    );
+                }
+            }
+
+            fullscreen_pass(
+                encoder,
+                output,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                &self.composite_pipeline,
+                &targets.composite_bind_group,
+            );
+        }
+    }
+
+    fn create_pipeline<T: Into<wgpu::ColorTargetState>>(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        entry_point: &str,
+        target: T,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[target.into()],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    fn fullscreen_pass(
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, bind_group, &[]);
+        rpass.draw(0..4, 0..1);
     }
 }
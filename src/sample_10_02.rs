@@ -0,0 +1,794 @@
+use bytemuck::{Pod, Zeroable};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Vertex {
+    _pos: [f32; 4],
+    _tex_coords: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Exposure {
+    _scale: f32,
+}
+
+fn vertex(x: f32, y: f32, tex_coords: [f32; 2]) -> Vertex {
+    Vertex {
+        _pos: [x, y, 0.0, 1.0],
+        _tex_coords: tex_coords,
+    }
+}
+
+fn create_vertices() -> Vec<Vertex> {
+    vec![
+        vertex(-1.0, -1.0, [0.0, 1.0]),
+        vertex(1.0, 1.0, [1.0, 0.0]),
+        vertex(1.0, -1.0, [1.0, 1.0]),
+        vertex(-1.0, 1.0, [0.0, 0.0]),
+    ]
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/10_02.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    // Pass a .hdr, .exr or 16-bit .png file as the first argument to view it instead.
+    let hdr_bytes = match std::env::args().nth(1) {
+        Some(path) => std::fs::read(path).unwrap(),
+        None => include_bytes!("../assets/sky.hdr").to_vec(),
+    };
+    let hdr_texture = texture::Texture::from_bytes(
+        &device,
+        &queue,
+        &hdr_bytes,
+        "hdr",
+        texture::TextureRole::Color,
+    )
+    .unwrap();
+
+    // 32-bit float textures can't be filtered without an extra feature.
+    let filterable = hdr_texture.format != wgpu::TextureFormat::Rgba32Float;
+
+    let texture_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: filterable,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        });
+
+    let hdr_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&hdr_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&hdr_texture.sampler),
+            },
+        ],
+        label: Some("hdr_bind_group"),
+    });
+
+    // Exposure in EV. The image is multiplied by 2^ev and then simply clipped by the swap chain,
+    // so lowering it reveals the details kept in the bright parts such as the sun.
+    let mut exposure = 0.0f32;
+    window.set_title(&format!(
+        "sample_10_02: {:?} EV {:+.1}",
+        hdr_texture.format, exposure
+    ));
+
+    let exposure_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("exposure"),
+        contents: bytemuck::bytes_of(&Exposure {
+            _scale: exposure.exp2(),
+        }),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let exposure_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("exposure_bind_group_layout"),
+        });
+
+    let exposure_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &exposure_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: exposure_buffer.as_entire_binding(),
+        }],
+        label: Some("exposure_bind_group"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&texture_bind_group_layout, &exposure_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    let vertex_data = create_vertices();
+    let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertex_data),
+        usage: wgpu::BufferUsage::VERTEX,
+    });
+
+    let index_data: Vec<u32> = vec![0, 1, 2, 3, 1, 0];
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Index Buffer"),
+        contents: bytemuck::cast_slice(&index_data),
+        usage: wgpu::BufferUsage::INDEX,
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+            }
+            // Up and Down change the exposure by half a stop.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let step = match keycode {
+                    VirtualKeyCode::Up => 0.5,
+                    VirtualKeyCode::Down => -0.5,
+                    _ => return,
+                };
+                exposure += step;
+                queue.write_buffer(
+                    &exposure_buffer,
+                    0,
+                    bytemuck::bytes_of(&Exposure {
+                        _scale: exposure.exp2(),
+                    }),
+                );
+                window.set_title(&format!(
+                    "sample_10_02: {:?} EV {:+.1}",
+                    hdr_texture.format, exposure
+                ));
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_vertex_buffer(0, vertex_buf.slice(..));
+                    rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+                    rpass.set_bind_group(0, &hdr_bind_group, &[]);
+                    rpass.set_bind_group(1, &exposure_bind_group, &[]);
+                    rpass.draw_indexed(0..index_data.len() as u32, 0, 0..1);
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    // Temporarily avoid srgb formats for the swapchain on the web
+    pollster::block_on(run(event_loop, window));
+}
+
+mod texture {
+    use std::{
+        borrow::Cow,
+        io::{BufReader, Cursor},
+        num::{NonZeroU32, NonZeroU8},
+    };
+
+    use anyhow::*;
+    use image::GenericImageView;
+
+    // The texture is held to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+        pub format: wgpu::TextureFormat,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+
+        fn is_srgb(self) -> bool {
+            match self {
+                TextureRole::Color => true,
+            }
+        }
+    }
+
+    const RADIANCE_MAGIC: &[u8] = b"#?";
+    const OPENEXR_MAGIC: &[u8] = &[0x76, 0x2f, 0x31, 0x01];
+
+    fn srgb_to_linear(c: f32) -> f32 {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    impl Texture {
+        // Radiance .hdr and OpenEXR files are recognized by their magic numbers, everything else
+        // goes through `image`.
+        pub fn from_bytes(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+            role: TextureRole,
+        ) -> Result<Self> {
+            if bytes.starts_with(RADIANCE_MAGIC) {
+                Self::from_radiance(device, queue, bytes, label)
+            } else if bytes.starts_with(OPENEXR_MAGIC) {
+                Self::from_openexr(device, queue, bytes, label)
+            } else {
+                let img = image::load_from_memory(bytes)?;
+                Self::from_image(device, queue, &img, Some(label), role)
+            }
+        }
+
+        // RGBE only has 8 bits of mantissa, so half floats lose nothing.
+        pub fn from_radiance(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+        ) -> Result<Self> {
+            let decoder = image::codecs::hdr::HdrDecoder::new(BufReader::new(Cursor::new(bytes)))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|image::Rgb([r, g, b])| [r, g, b, 1.0])
+                .collect::<Vec<_>>();
+
+            Self::from_float_pixels(
+                device,
+                queue,
+                &pixels,
+                (metadata.width, metadata.height),
+                Some(label),
+                wgpu::TextureFormat::Rgba16Float,
+            )
+        }
+
+        // Half float channels are uploaded as `Rgba16Float` and full float channels as `Rgba32Float`.
+        pub fn from_openexr(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+        ) -> Result<Self> {
+            use exr::prelude::traits::*;
+            use exr::prelude::{RgbaChannels, SampleType, Vec2};
+
+            struct Pixels {
+                width: usize,
+                full_precision: bool,
+                data: Vec<[f32; 4]>,
+            }
+
+            let image = read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .rgba_channels(
+                    |resolution: Vec2<usize>, channels: &RgbaChannels| Pixels {
+                        width: resolution.width(),
+                        full_precision: channels.0.sample_type == SampleType::F32,
+                        data: vec![[0.0; 4]; resolution.width() * resolution.height()],
+                    },
+                    |pixels: &mut Pixels,
+                     position: Vec2<usize>,
+                     (r, g, b, a): (f32, f32, f32, f32)| {
+                        pixels.data[position.y() * pixels.width + position.x()] = [r, g, b, a];
+                    },
+                )
+                .first_valid_layer()
+                .all_attributes()
+                .from_buffered(Cursor::new(bytes))?;
+
+            let pixels = image.layer_data.channel_data.pixels;
+            let size = image.layer_data.size;
+            let format = if pixels.full_precision {
+                wgpu::TextureFormat::Rgba32Float
+            } else {
+                wgpu::TextureFormat::Rgba16Float
+            };
+
+            Self::from_float_pixels(
+                device,
+                queue,
+                &pixels.data,
+                (size.width() as u32, size.height() as u32),
+                Some(label),
+                format,
+            )
+        }
+
+        // 16-bit sources become half floats. wgpu has no 16-bit normalized format yet and
+        // `Rgba32Float` cannot be filtered, so this is the closest filterable format. A half float
+        // only has an 11-bit significand, so the finest steps of the source are lost, although
+        // that is still more precision than 8 bits, spread evenly over dark and bright values.
+        pub fn from_image(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            img: &image::DynamicImage,
+            label: Option<&str>,
+            role: TextureRole,
+        ) -> Result<Self> {
+            let dimensions = img.dimensions();
+
+            match img.color() {
+                image::ColorType::L16
+                | image::ColorType::La16
+                | image::ColorType::Rgb16
+                | image::ColorType::Rgba16 => {
+                    let pixels = img
+                        .to_rgba16()
+                        .pixels()
+                        .map(|image::Rgba(rgba)| {
+                            let mut color = [0.0; 4];
+                            for (i, c) in rgba.iter().enumerate() {
+                                color[i] = *c as f32 / u16::MAX as f32;
+                                if i < 3 && role.is_srgb() {
+                                    color[i] = srgb_to_linear(color[i]);
+                                }
+                            }
+                            color
+                        })
+                        .collect::<Vec<_>>();
+
+                    return Self::from_float_pixels(
+                        device,
+                        queue,
+                        &pixels,
+                        dimensions,
+                        label,
+                        wgpu::TextureFormat::Rgba16Float,
+                    );
+                }
+                _ => {}
+            }
+
+            let rgba = img.to_rgba8();
+
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    rows_per_image: NonZeroU32::new(dimensions.1),
+                },
+                size,
+            );
+
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&Self::sampler_descriptor(true));
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+                format,
+            })
+        }
+
+        // Upload linear float pixels as either `Rgba16Float` or `Rgba32Float`.
+        pub fn from_float_pixels(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            pixels: &[[f32; 4]],
+            dimensions: (u32, u32),
+            label: Option<&str>,
+            format: wgpu::TextureFormat,
+        ) -> Result<Self> {
+            let (data, bytes_per_pixel) = match format {
+                wgpu::TextureFormat::Rgba16Float => {
+                    let halves = pixels
+                        .iter()
+                        .flatten()
+                        .map(|c| half::f16::from_f32(*c).to_bits())
+                        .collect::<Vec<u16>>();
+                    (bytemuck::cast_slice(&halves).to_vec(), 8)
+                }
+                wgpu::TextureFormat::Rgba32Float => (bytemuck::cast_slice(pixels).to_vec(), 16),
+                _ => bail!("{:?} is not a float format", format),
+            };
+
+            // Only half floats are filterable (and renderable for the mip chain) out of the box.
+            let filterable = format == wgpu::TextureFormat::Rgba16Float;
+
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let mip_level_count = if filterable {
+                Self::mip_level_count(dimensions.0, dimensions.1)
+            } else {
+                1
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes_per_pixel * dimensions.0),
+                    rows_per_image: NonZeroU32::new(dimensions.1),
+                },
+                size,
+            );
+
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&Self::sampler_descriptor(filterable));
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+                format,
+            })
+        }
+
+        fn sampler_descriptor(filterable: bool) -> wgpu::SamplerDescriptor<'static> {
+            let filter = if filterable {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            };
+
+            wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: if filterable { NonZeroU8::new(16) } else { None },
+                ..Default::default()
+            }
+        }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
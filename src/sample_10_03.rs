@@ -0,0 +1,917 @@
+use bytemuck::{Pod, Zeroable};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Vertex {
+    _pos: [f32; 2],
+    _tex_coords: [f32; 2],
+}
+
+fn vertex(x: f32, y: f32, tex_coords: [f32; 2]) -> Vertex {
+    Vertex {
+        _pos: [x, y],
+        _tex_coords: tex_coords,
+    }
+}
+
+// Two quads side by side. The left one shows the KTX2 texture, the right one the DDS texture.
+fn create_vertices() -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for &left in &[-0.9, 0.1] {
+        let right = left + 0.8;
+        vertices.push(vertex(left, 0.4, [0.0, 0.0]));
+        vertices.push(vertex(right, -0.4, [1.0, 1.0]));
+        vertices.push(vertex(right, 0.4, [1.0, 0.0]));
+        vertices.push(vertex(left, -0.4, [0.0, 1.0]));
+    }
+    vertices
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Enable whichever block compression families the adapter supports. Textures in the
+    // other formats are decoded on the CPU when they are loaded.
+    let features = adapter.features() & texture::COMPRESSION_FEATURES;
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/10_03.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    // Pass up to two .ktx2 or .dds files as arguments to view them instead.
+    let mut args = std::env::args().skip(1);
+    let left_bytes = match args.next() {
+        Some(path) => std::fs::read(path).unwrap(),
+        None => include_bytes!("../assets/bricks_bc1.ktx2").to_vec(),
+    };
+    let right_bytes = match args.next() {
+        Some(path) => std::fs::read(path).unwrap(),
+        None => include_bytes!("../assets/dot_bc3.dds").to_vec(),
+    };
+    let textures = [
+        texture::Texture::from_bytes(&device, &queue, &left_bytes, "left").unwrap(),
+        texture::Texture::from_bytes(&device, &queue, &right_bytes, "right").unwrap(),
+    ];
+
+    window.set_title(&format!(
+        "sample_10_03: {:?} / {:?}",
+        textures[0].format, textures[1].format
+    ));
+
+    let texture_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        });
+
+    let texture_bind_groups = textures
+        .iter()
+        .map(|texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+                label: Some("texture_bind_group"),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&texture_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            // Blend so that the alpha channel of BC2/BC3/BC7 textures is visible.
+            targets: &[wgpu::ColorTargetState {
+                format: swapchain_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    let vertex_data = create_vertices();
+    let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertex_data),
+        usage: wgpu::BufferUsage::VERTEX,
+    });
+
+    let index_data: Vec<u32> = vec![0, 1, 2, 3, 1, 0];
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Index Buffer"),
+        contents: bytemuck::cast_slice(&index_data),
+        usage: wgpu::BufferUsage::INDEX,
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &pipeline_layout, &textures);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_vertex_buffer(0, vertex_buf.slice(..));
+                    rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+                    for (i, bind_group) in texture_bind_groups.iter().enumerate() {
+                        rpass.set_bind_group(0, bind_group, &[]);
+                        rpass.draw_indexed(0..index_data.len() as u32, 4 * i as i32, 0..1);
+                    }
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    // Temporarily avoid srgb formats for the swapchain on the web
+    pollster::block_on(run(event_loop, window));
+}
+
+mod texture {
+    use std::{
+        borrow::Cow,
+        io::Cursor,
+        num::{NonZeroU32, NonZeroU8},
+    };
+
+    use anyhow::*;
+    use image::GenericImageView;
+
+    // The texture is held to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+        pub format: wgpu::TextureFormat,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
+    // Every block compression family a KTX2 or DDS file can contain.
+    pub const COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::from_bits_truncate(
+        wgpu::Features::TEXTURE_COMPRESSION_BC.bits()
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2.bits()
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR.bits(),
+    );
+
+    const KTX2_MAGIC: &[u8] = &[
+        0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+    ];
+    const DDS_MAGIC: &[u8] = b"DDS ";
+
+    // The raw blocks of a compressed image, one entry per mip level starting at the largest.
+    struct CompressedImage {
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    }
+
+    fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+        use ktx2::Format as K;
+        use wgpu::TextureFormat as W;
+        Some(match format {
+            K::BC1_RGBA_UNORM_BLOCK => W::Bc1RgbaUnorm,
+            K::BC1_RGBA_SRGB_BLOCK => W::Bc1RgbaUnormSrgb,
+            K::BC2_UNORM_BLOCK => W::Bc2RgbaUnorm,
+            K::BC2_SRGB_BLOCK => W::Bc2RgbaUnormSrgb,
+            K::BC3_UNORM_BLOCK => W::Bc3RgbaUnorm,
+            K::BC3_SRGB_BLOCK => W::Bc3RgbaUnormSrgb,
+            K::BC4_UNORM_BLOCK => W::Bc4RUnorm,
+            K::BC4_SNORM_BLOCK => W::Bc4RSnorm,
+            K::BC5_UNORM_BLOCK => W::Bc5RgUnorm,
+            K::BC5_SNORM_BLOCK => W::Bc5RgSnorm,
+            K::BC6H_UFLOAT_BLOCK => W::Bc6hRgbUfloat,
+            K::BC6H_SFLOAT_BLOCK => W::Bc6hRgbSfloat,
+            K::BC7_UNORM_BLOCK => W::Bc7RgbaUnorm,
+            K::BC7_SRGB_BLOCK => W::Bc7RgbaUnormSrgb,
+            K::ETC2_R8G8B8_UNORM_BLOCK => W::Etc2RgbUnorm,
+            K::ETC2_R8G8B8_SRGB_BLOCK => W::Etc2RgbUnormSrgb,
+            K::ETC2_R8G8B8A1_UNORM_BLOCK => W::Etc2RgbA1Unorm,
+            K::ETC2_R8G8B8A1_SRGB_BLOCK => W::Etc2RgbA1UnormSrgb,
+            K::ETC2_R8G8B8A8_UNORM_BLOCK => W::Etc2RgbA8Unorm,
+            K::ETC2_R8G8B8A8_SRGB_BLOCK => W::Etc2RgbA8UnormSrgb,
+            K::EAC_R11_UNORM_BLOCK => W::EacRUnorm,
+            K::EAC_R11_SNORM_BLOCK => W::EacRSnorm,
+            K::EAC_R11G11_UNORM_BLOCK => W::EtcRgUnorm,
+            K::EAC_R11G11_SNORM_BLOCK => W::EtcRgSnorm,
+            K::ASTC_4x4_UNORM_BLOCK => W::Astc4x4RgbaUnorm,
+            K::ASTC_4x4_SRGB_BLOCK => W::Astc4x4RgbaUnormSrgb,
+            K::ASTC_5x4_UNORM_BLOCK => W::Astc5x4RgbaUnorm,
+            K::ASTC_5x4_SRGB_BLOCK => W::Astc5x4RgbaUnormSrgb,
+            K::ASTC_5x5_UNORM_BLOCK => W::Astc5x5RgbaUnorm,
+            K::ASTC_5x5_SRGB_BLOCK => W::Astc5x5RgbaUnormSrgb,
+            K::ASTC_6x5_UNORM_BLOCK => W::Astc6x5RgbaUnorm,
+            K::ASTC_6x5_SRGB_BLOCK => W::Astc6x5RgbaUnormSrgb,
+            K::ASTC_6x6_UNORM_BLOCK => W::Astc6x6RgbaUnorm,
+            K::ASTC_6x6_SRGB_BLOCK => W::Astc6x6RgbaUnormSrgb,
+            K::ASTC_8x5_UNORM_BLOCK => W::Astc8x5RgbaUnorm,
+            K::ASTC_8x5_SRGB_BLOCK => W::Astc8x5RgbaUnormSrgb,
+            K::ASTC_8x6_UNORM_BLOCK => W::Astc8x6RgbaUnorm,
+            K::ASTC_8x6_SRGB_BLOCK => W::Astc8x6RgbaUnormSrgb,
+            K::ASTC_8x8_UNORM_BLOCK => W::Astc8x8RgbaUnorm,
+            K::ASTC_8x8_SRGB_BLOCK => W::Astc8x8RgbaUnormSrgb,
+            K::ASTC_10x5_UNORM_BLOCK => W::Astc10x5RgbaUnorm,
+            K::ASTC_10x5_SRGB_BLOCK => W::Astc10x5RgbaUnormSrgb,
+            K::ASTC_10x6_UNORM_BLOCK => W::Astc10x6RgbaUnorm,
+            K::ASTC_10x6_SRGB_BLOCK => W::Astc10x6RgbaUnormSrgb,
+            K::ASTC_10x8_UNORM_BLOCK => W::Astc10x8RgbaUnorm,
+            K::ASTC_10x8_SRGB_BLOCK => W::Astc10x8RgbaUnormSrgb,
+            K::ASTC_10x10_UNORM_BLOCK => W::Astc10x10RgbaUnorm,
+            K::ASTC_10x10_SRGB_BLOCK => W::Astc10x10RgbaUnormSrgb,
+            K::ASTC_12x10_UNORM_BLOCK => W::Astc12x10RgbaUnorm,
+            K::ASTC_12x10_SRGB_BLOCK => W::Astc12x10RgbaUnormSrgb,
+            K::ASTC_12x12_UNORM_BLOCK => W::Astc12x12RgbaUnorm,
+            K::ASTC_12x12_SRGB_BLOCK => W::Astc12x12RgbaUnormSrgb,
+            _ => return None,
+        })
+    }
+
+    fn dxgi_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+        use ddsfile::DxgiFormat as D;
+        use wgpu::TextureFormat as W;
+        Some(match format {
+            D::BC1_UNorm => W::Bc1RgbaUnorm,
+            D::BC1_UNorm_sRGB => W::Bc1RgbaUnormSrgb,
+            D::BC2_UNorm => W::Bc2RgbaUnorm,
+            D::BC2_UNorm_sRGB => W::Bc2RgbaUnormSrgb,
+            D::BC3_UNorm => W::Bc3RgbaUnorm,
+            D::BC3_UNorm_sRGB => W::Bc3RgbaUnormSrgb,
+            D::BC4_UNorm => W::Bc4RUnorm,
+            D::BC4_SNorm => W::Bc4RSnorm,
+            D::BC5_UNorm => W::Bc5RgUnorm,
+            D::BC5_SNorm => W::Bc5RgSnorm,
+            D::BC6H_UF16 => W::Bc6hRgbUfloat,
+            D::BC6H_SF16 => W::Bc6hRgbSfloat,
+            D::BC7_UNorm => W::Bc7RgbaUnorm,
+            D::BC7_UNorm_sRGB => W::Bc7RgbaUnormSrgb,
+            _ => return None,
+        })
+    }
+
+    // Legacy DDS headers have no way to flag sRGB, so these are always treated as linear.
+    fn d3d_format(format: ddsfile::D3DFormat) -> Option<wgpu::TextureFormat> {
+        use ddsfile::D3DFormat as D;
+        use wgpu::TextureFormat as W;
+        Some(match format {
+            D::DXT1 => W::Bc1RgbaUnorm,
+            D::DXT3 => W::Bc2RgbaUnorm,
+            D::DXT5 => W::Bc3RgbaUnorm,
+            _ => return None,
+        })
+    }
+
+    // The byte size of one mip level of a block compressed image.
+    fn level_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+        let info = format.describe();
+        let (block_width, block_height) = info.block_dimensions;
+        let blocks_wide = (width >> level).max(1).div_ceil(block_width as u32);
+        let blocks_high = (height >> level).max(1).div_ceil(block_height as u32);
+        (blocks_wide * blocks_high) as usize * info.block_size as usize
+    }
+
+    fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some() {
+            bail!("Supercompressed KTX2 files are not supported");
+        }
+        if header.face_count != 1 || header.layer_count > 1 || header.pixel_depth > 1 {
+            bail!("Only single 2D images are supported");
+        }
+        let format = header
+            .format
+            .and_then(ktx2_format)
+            .ok_or_else(|| anyhow!("Unsupported KTX2 format {:?}", header.format))?;
+
+        Ok(CompressedImage {
+            format,
+            width: header.pixel_width,
+            height: header.pixel_height,
+            levels: reader.levels().map(|level| level.to_vec()).collect(),
+        })
+    }
+
+    fn parse_dds(bytes: &[u8]) -> Result<CompressedImage> {
+        let dds = ddsfile::Dds::read(&mut Cursor::new(bytes))?;
+        let format = dds
+            .get_dxgi_format()
+            .and_then(dxgi_format)
+            .or_else(|| dds.get_d3d_format().and_then(d3d_format))
+            .ok_or_else(|| anyhow!("Unsupported DDS format"))?;
+        let (width, height) = (dds.get_width(), dds.get_height());
+
+        // DDS stores all mip levels of a layer back to back.
+        let mut data = dds.get_data(0)?;
+        let mut levels = Vec::new();
+        for level in 0..dds.get_num_mipmap_levels() {
+            let size = level_size(format, width, height, level);
+            if data.len() < size {
+                bail!("DDS file is truncated");
+            }
+            let (level_data, rest) = data.split_at(size);
+            levels.push(level_data.to_vec());
+            data = rest;
+        }
+
+        Ok(CompressedImage {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    // texture2ddecoder has no BC2 decoder. A BC2 block is 64 bits of explicit 4-bit alpha, one
+    // texel per nibble starting at the low one, followed by a BC1 color block that is always in
+    // its four color mode, as the alpha never comes from the punch-through.
+    fn decode_bc2(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        pixels: &mut [u32],
+    ) -> std::result::Result<(), &'static str> {
+        let blocks_wide = width.div_ceil(4);
+        let blocks_high = height.div_ceil(4);
+        if data.len() < blocks_wide * blocks_high * 16 {
+            return Err("Not enough data to decode image!");
+        }
+
+        // RGB565 widened to 8 bits per channel by repeating the high bits.
+        let expand = |color: u16| {
+            let r = (color >> 11) & 0x1f;
+            let g = (color >> 5) & 0x3f;
+            let b = color & 0x1f;
+            [
+                (r << 3) | (r >> 2),
+                (g << 2) | (g >> 4),
+                (b << 3) | (b >> 2),
+            ]
+        };
+        let blend = |a: [u16; 3], b: [u16; 3]| {
+            [
+                (2 * a[0] + b[0]) / 3,
+                (2 * a[1] + b[1]) / 3,
+                (2 * a[2] + b[2]) / 3,
+            ]
+        };
+
+        for (i, block) in data
+            .chunks_exact(16)
+            .take(blocks_wide * blocks_high)
+            .enumerate()
+        {
+            let x0 = i % blocks_wide * 4;
+            let y0 = i / blocks_wide * 4;
+            let color0 = expand(u16::from_le_bytes([block[8], block[9]]));
+            let color1 = expand(u16::from_le_bytes([block[10], block[11]]));
+            let palette = [color0, color1, blend(color0, color1), blend(color1, color0)];
+            let indices = u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+
+            for texel in 0..16 {
+                let x = x0 + texel % 4;
+                let y = y0 + texel / 4;
+                if x >= width || y >= height {
+                    continue;
+                }
+                let [r, g, b] = palette[((indices >> (2 * texel)) & 3) as usize];
+                // 4-bit alpha scaled up to 8 bits, so that 15 becomes 255.
+                let a = ((block[texel / 2] >> (texel % 2 * 4)) & 0xf) * 17;
+                pixels[y * width + x] = u32::from_le_bytes([b as u8, g as u8, r as u8, a]);
+            }
+        }
+        Ok(())
+    }
+
+    // Decode one mip level to RGBA8. The decoder writes BGRA packed into `u32`s.
+    fn decode_level(
+        format: wgpu::TextureFormat,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>> {
+        use wgpu::TextureFormat as W;
+        let (w, h) = (width as usize, height as usize);
+        let mut pixels = vec![0u32; w * h];
+        match format {
+            W::Bc1RgbaUnorm | W::Bc1RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc1(data, w, h, &mut pixels)
+            }
+            W::Bc2RgbaUnorm | W::Bc2RgbaUnormSrgb => decode_bc2(data, w, h, &mut pixels),
+            W::Bc3RgbaUnorm | W::Bc3RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc3(data, w, h, &mut pixels)
+            }
+            W::Bc4RUnorm | W::Bc4RSnorm => texture2ddecoder::decode_bc4(data, w, h, &mut pixels),
+            W::Bc5RgUnorm | W::Bc5RgSnorm => texture2ddecoder::decode_bc5(data, w, h, &mut pixels),
+            W::Bc6hRgbUfloat => texture2ddecoder::decode_bc6(data, w, h, &mut pixels, false),
+            W::Bc6hRgbSfloat => texture2ddecoder::decode_bc6(data, w, h, &mut pixels, true),
+            W::Bc7RgbaUnorm | W::Bc7RgbaUnormSrgb => {
+                texture2ddecoder::decode_bc7(data, w, h, &mut pixels)
+            }
+            W::Etc2RgbUnorm | W::Etc2RgbUnormSrgb => {
+                texture2ddecoder::decode_etc2_rgb(data, w, h, &mut pixels)
+            }
+            W::Etc2RgbA1Unorm | W::Etc2RgbA1UnormSrgb => {
+                texture2ddecoder::decode_etc2_rgba1(data, w, h, &mut pixels)
+            }
+            W::Etc2RgbA8Unorm | W::Etc2RgbA8UnormSrgb => {
+                texture2ddecoder::decode_etc2_rgba8(data, w, h, &mut pixels)
+            }
+            W::EacRUnorm => texture2ddecoder::decode_eacr(data, w, h, &mut pixels),
+            W::EacRSnorm => texture2ddecoder::decode_eacr_signed(data, w, h, &mut pixels),
+            W::EtcRgUnorm => texture2ddecoder::decode_eacrg(data, w, h, &mut pixels),
+            W::EtcRgSnorm => texture2ddecoder::decode_eacrg_signed(data, w, h, &mut pixels),
+            // Everything else that reaches here is ASTC.
+            _ => {
+                let (block_width, block_height) = format.describe().block_dimensions;
+                texture2ddecoder::decode_astc(
+                    data,
+                    w,
+                    h,
+                    block_width as usize,
+                    block_height as usize,
+                    &mut pixels,
+                )
+            }
+        }
+        .map_err(|e| anyhow!("Failed to decode {:?}: {}", format, e))?;
+
+        Ok(pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect())
+    }
+
+    impl Texture {
+        // KTX2 and DDS files are recognized by their magic numbers, everything else goes
+        // through `image`.
+        pub fn from_bytes(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+        ) -> Result<Self> {
+            if bytes.starts_with(KTX2_MAGIC) {
+                Self::from_compressed(device, queue, parse_ktx2(bytes)?, label)
+            } else if bytes.starts_with(DDS_MAGIC) {
+                Self::from_compressed(device, queue, parse_dds(bytes)?, label)
+            } else {
+                let img = image::load_from_memory(bytes)?;
+                Self::from_image(device, queue, &img, Some(label), TextureRole::Color)
+            }
+        }
+
+        // Upload the blocks as they are when the device supports the format. Otherwise decode
+        // every mip level to RGBA8 on the CPU, keeping the pre-built mip chain.
+        fn from_compressed(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            image: CompressedImage,
+            label: &str,
+        ) -> Result<Self> {
+            let info = image.format.describe();
+            let (block_width, block_height) = info.block_dimensions;
+            let block_width = block_width as u32;
+            let block_height = block_height as u32;
+            // The base level of a compressed texture must be a whole number of blocks.
+            let native = device.features().contains(info.required_features)
+                && image.width.is_multiple_of(block_width)
+                && image.height.is_multiple_of(block_height);
+
+            let format = match (native, info.srgb) {
+                (true, _) => image.format,
+                (false, true) => wgpu::TextureFormat::Rgba8UnormSrgb,
+                (false, false) => wgpu::TextureFormat::Rgba8Unorm,
+            };
+            let mip_level_count = image.levels.len() as u32;
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: image.width,
+                    height: image.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            });
+
+            for (level, data) in image.levels.iter().enumerate() {
+                let width = (image.width >> level).max(1);
+                let height = (image.height >> level).max(1);
+                let mip_level = level as u32;
+
+                if native {
+                    // Copies of compressed levels cover whole blocks, even for the smallest
+                    // levels which are only a few texels wide.
+                    let blocks_wide = width.div_ceil(block_width);
+                    let blocks_high = height.div_ceil(block_height);
+                    queue.write_texture(
+                        wgpu::ImageCopyTexture {
+                            texture: &texture,
+                            mip_level,
+                            origin: wgpu::Origin3d::ZERO,
+                        },
+                        data,
+                        wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: NonZeroU32::new(blocks_wide * info.block_size as u32),
+                            rows_per_image: None,
+                        },
+                        wgpu::Extent3d {
+                            width: blocks_wide * block_width,
+                            height: blocks_high * block_height,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                } else {
+                    let rgba = decode_level(image.format, data, width, height)?;
+                    queue.write_texture(
+                        wgpu::ImageCopyTexture {
+                            texture: &texture,
+                            mip_level,
+                            origin: wgpu::Origin3d::ZERO,
+                        },
+                        &rgba,
+                        wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: NonZeroU32::new(4 * width),
+                            rows_per_image: NonZeroU32::new(height),
+                        },
+                        wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+            }
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&Self::sampler_descriptor());
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+                format,
+            })
+        }
+
+        pub fn from_image(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            img: &image::DynamicImage,
+            label: Option<&str>,
+            role: TextureRole,
+        ) -> Result<Self> {
+            let rgba = img.to_rgba8();
+            let dimensions = img.dimensions();
+
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    rows_per_image: NonZeroU32::new(dimensions.1),
+                },
+                size,
+            );
+
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&Self::sampler_descriptor());
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+                format,
+            })
+        }
+
+        // Trilinear filtering with anisotropy so that minified textures don't shimmer.
+        fn sampler_descriptor() -> wgpu::SamplerDescriptor<'static> {
+            wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
+                ..Default::default()
+            }
+        }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
@@ -0,0 +1,1293 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector4};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[path = "common/fog.rs"]
+mod fog;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DirectionLight {
+    _eye_position: [f32; 3],
+    _pad0: f32,
+    _directional_light_direction: [f32; 3],
+    _pad1: f32,
+    _directianal_light_color: [f32; 3],
+    _pad2: f32,
+    _ambient_color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkySource {
+    SixFaces,
+    Cross,
+    Equirectangular,
+}
+
+// How the teapots are shaded. Chrome and glass take everything they show from the sky.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SurfaceMaterial {
+    Phong,
+    Chrome,
+    Glass,
+}
+
+impl SurfaceMaterial {
+    fn next(self) -> Self {
+        match self {
+            SurfaceMaterial::Phong => SurfaceMaterial::Chrome,
+            SurfaceMaterial::Chrome => SurfaceMaterial::Glass,
+            SurfaceMaterial::Glass => SurfaceMaterial::Phong,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct MaterialUniform {
+    _ior: [f32; 3],
+    _surface: u32,
+    _tint: [f32; 3],
+    _pad: f32,
+}
+
+// The index of refraction of glass for green light.
+const GLASS_IOR: f32 = 1.5;
+// D steps through these. Red bends a little less than green and blue a little more.
+const DISPERSIONS: [f32; 3] = [0.0, 0.02, 0.05];
+
+fn material_uniform(material: SurfaceMaterial, dispersion: f32) -> MaterialUniform {
+    MaterialUniform {
+        _ior: [GLASS_IOR - dispersion, GLASS_IOR, GLASS_IOR + dispersion],
+        _surface: material as u32,
+        // The reflectance of chrome at normal incidence, or the color glass filters.
+        _tint: match material {
+            SurfaceMaterial::Glass => [0.95, 1.0, 0.97],
+            _ => [0.55, 0.56, 0.55],
+        },
+        _pad: 0.0,
+    }
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// An orbit camera around the teapot. Yaw and pitch are in degrees.
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Camera {
+    const DISTANCE: f32 = 3.0;
+
+    fn eye(&self) -> Point3<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Point3::new(
+            Self::DISTANCE * pitch.cos() * yaw.sin(),
+            Self::DISTANCE * pitch.sin(),
+            Self::DISTANCE * pitch.cos() * yaw.cos(),
+        )
+    }
+
+    fn projection(width: u32, height: u32) -> Matrix4<f32> {
+        OPENGL_TO_WGPU_MATRIX
+            * cgmath::perspective(
+                cgmath::Deg(60.0),
+                width as f32 / height.max(1) as f32,
+                0.1,
+                100.0,
+            )
+    }
+
+    fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(
+            self.eye(),
+            Point3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_y(),
+        )
+    }
+
+    fn view_projection(&self, width: u32, height: u32) -> Matrix {
+        Matrix {
+            _matrix: (Self::projection(width, height) * self.view()).into(),
+        }
+    }
+
+    // The sky is infinitely far away, so only the rotation of the camera matters.
+    fn sky_inverse_view_projection(&self, width: u32, height: u32) -> Matrix {
+        let mut rotation = self.view();
+        rotation.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        Matrix {
+            _matrix: (Self::projection(width, height) * rotation)
+                .invert()
+                .unwrap()
+                .into(),
+        }
+    }
+}
+
+fn direction_light(eye: Point3<f32>) -> DirectionLight {
+    DirectionLight {
+        _eye_position: eye.into(),
+        _pad0: 0.0,
+        _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
+            1.0f32, -1.0, 1.0,
+        ))
+        .into(),
+        _pad1: 0.0,
+        _directianal_light_color: [0.5, 0.5, 0.5],
+        _pad2: 0.0,
+        _ambient_color: [0.3, 0.3, 0.3],
+    }
+}
+
+// The teapot at the center plus two rings around the camera orbit, so that fog has a range
+// of distances to show.
+fn teapot_offsets() -> Vec<cgmath::Vector3<f32>> {
+    let mut offsets = vec![cgmath::vec3(0.0, 0.0, 0.0)];
+    for &(radius, count) in &[(5.0f32, 8), (9.0, 12)] {
+        for i in 0..count {
+            let angle = i as f32 / count as f32 * 2.0 * std::f32::consts::PI;
+            offsets.push(cgmath::vec3(
+                radius * angle.cos(),
+                0.0,
+                radius * angle.sin(),
+            ));
+        }
+    }
+    offsets
+}
+
+fn title(
+    sky_source: SkySource,
+    material: SurfaceMaterial,
+    dispersion: f32,
+    fog: &fog::Fog,
+) -> String {
+    format!(
+        "sample_10_04: {:?}, {:?} dispersion {:.2}, {}",
+        sky_source,
+        material,
+        dispersion,
+        fog.title()
+    )
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let teapot = include_bytes!("../assets/teapot.glb");
+    let (document, buffers, _images) = gltf::import_slice(teapot).unwrap();
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/fog.wgsl"),
+            include_str!("../assets/10_04.wgsl")
+        ))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let sky_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("skybox"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/skybox.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    // The same sky from the three kinds of sources a cube map can be built from.
+    let faces: [&[u8]; 6] = [
+        include_bytes!("../assets/sky_px.png"),
+        include_bytes!("../assets/sky_nx.png"),
+        include_bytes!("../assets/sky_py.png"),
+        include_bytes!("../assets/sky_ny.png"),
+        include_bytes!("../assets/sky_pz.png"),
+        include_bytes!("../assets/sky_nz.png"),
+    ];
+    let faces = faces
+        .iter()
+        .map(|bytes| image::load_from_memory(bytes).unwrap())
+        .collect::<Vec<_>>();
+    let cross = image::load_from_memory(include_bytes!("../assets/sky_cross.png")).unwrap();
+    let sky_sources = [
+        SkySource::SixFaces,
+        SkySource::Cross,
+        SkySource::Equirectangular,
+    ];
+    let sky_textures = [
+        texture::Texture::create_cube_from_faces(&device, &queue, &faces, "sky faces").unwrap(),
+        texture::Texture::create_cube_from_cross(&device, &queue, &cross, "sky cross").unwrap(),
+        texture::Texture::create_cube_from_equirectangular(
+            &device,
+            &queue,
+            include_bytes!("../assets/sky.hdr"),
+            512,
+            "sky equirectangular",
+        )
+        .unwrap(),
+    ];
+
+    let mut camera = Camera {
+        yaw: 30.0,
+        pitch: 15.0,
+    };
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&camera.view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let sky_matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("sky matrix"),
+        contents: bytemuck::bytes_of(&camera.sky_inverse_view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    // One matrix per primitive of every teapot, in that order.
+    let offsets = teapot_offsets();
+    let draw_count = offsets.len() * scene.primitives.len();
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: draw_count as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, &offset) in offsets.iter().enumerate() {
+        // The teapot is authored in centimeters.
+        let model = Matrix4::from_translation(offset + cgmath::vec3(0.0, -0.25, 0.0))
+            * Matrix4::from_scale(0.01);
+        for (j, primitive) in scene.primitives.iter().enumerate() {
+            queue.write_buffer(
+                &local_matrix_buffer,
+                (i * scene.primitives.len() + j) as wgpu::BufferAddress
+                    * wgpu::BIND_BUFFER_ALIGNMENT,
+                bytemuck::bytes_of(&Matrix {
+                    _matrix: (model * primitive.transform).into(),
+                }),
+            );
+        }
+    }
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    // The light and the material of the teapots.
+    let directional_light_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<MaterialUniform>() as wgpu::BufferAddress
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+            label: None,
+        });
+
+    let cube_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("cube_bind_group_layout"),
+        });
+
+    // The fog parameters and the sky the fog can take its color from.
+    let fog_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            fog::Fog::layout_entry(0),
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler {
+                    comparison: false,
+                    filtering: true,
+                },
+                count: None,
+            },
+        ],
+        label: Some("fog_bind_group_layout"),
+    });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let sky_matrix_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: sky_matrix_buffer.as_entire_binding(),
+        }],
+        label: Some("sky_matrix_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("light"),
+        contents: bytemuck::bytes_of(&direction_light(camera.eye())),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let mut material = SurfaceMaterial::Glass;
+    let mut dispersion = DISPERSIONS[1];
+    let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("material"),
+        contents: bytemuck::bytes_of(&material_uniform(material, dispersion)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &directional_light_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &directional_light_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
+                }),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: material_buffer.as_entire_binding(),
+            },
+        ],
+        label: None,
+    });
+
+    let sky_bind_groups = sky_textures
+        .iter()
+        .map(|sky_texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &cube_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&sky_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sky_texture.sampler),
+                    },
+                ],
+                label: Some("sky_bind_group"),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut fog = fog::Fog::new();
+    let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("fog"),
+        contents: bytemuck::bytes_of(&fog.uniform()),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let fog_bind_groups = sky_textures
+        .iter()
+        .map(|sky_texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &fog_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: fog_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&sky_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&sky_texture.sampler),
+                    },
+                ],
+                label: Some("fog_bind_group"),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut current_sky = 2;
+    window.set_title(&title(sky_sources[current_sky], material, dispersion, &fog));
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &directional_light_bind_group_layout,
+            &fog_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let sky_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("skybox"),
+        bind_group_layouts: &[&uniform_bind_group_layout, &cube_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    // Drawn after the scene, so the depth test rejects every pixel the teapot covers.
+    let sky_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("skybox"),
+        layout: Some(&sky_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &sky_shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &sky_shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut depth_texture =
+        texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &sky_shader,
+            &pipeline_layout,
+            &sky_pipeline_layout,
+        );
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                depth_texture =
+                    texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                queue.write_buffer(
+                    &sky_matrix_buffer,
+                    0,
+                    bytemuck::bytes_of(
+                        &camera.sky_inverse_view_projection(sc_desc.width, sc_desc.height),
+                    ),
+                );
+            }
+            // Arrow keys orbit the camera, 1-3 switch between the cube map sources. F steps
+            // through the distance fog modes, H toggles height fog, S toggles sky in-scattering
+            // and Page Up/Down thicken and thin the fog. M switches between the Phong, chrome and
+            // glass materials and D steps through the amounts of dispersion.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::Left => camera.yaw -= 10.0,
+                    VirtualKeyCode::Right => camera.yaw += 10.0,
+                    VirtualKeyCode::Up => camera.pitch = (camera.pitch + 10.0).min(80.0),
+                    VirtualKeyCode::Down => camera.pitch = (camera.pitch - 10.0).max(-80.0),
+                    VirtualKeyCode::Key1 => current_sky = 0,
+                    VirtualKeyCode::Key2 => current_sky = 1,
+                    VirtualKeyCode::Key3 => current_sky = 2,
+                    VirtualKeyCode::F => fog.mode = fog.mode.next(),
+                    VirtualKeyCode::H => fog.height = !fog.height,
+                    VirtualKeyCode::S => fog.sky_scattering = !fog.sky_scattering,
+                    VirtualKeyCode::PageUp => fog.thicken(1.25),
+                    VirtualKeyCode::PageDown => fog.thicken(1.0 / 1.25),
+                    VirtualKeyCode::M => material = material.next(),
+                    VirtualKeyCode::D => {
+                        let index = DISPERSIONS
+                            .iter()
+                            .position(|&d| d == dispersion)
+                            .map_or(0, |index| (index + 1) % DISPERSIONS.len());
+                        dispersion = DISPERSIONS[index];
+                    }
+                    _ => return,
+                }
+                window.set_title(&title(sky_sources[current_sky], material, dispersion, &fog));
+                queue.write_buffer(&fog_buffer, 0, bytemuck::bytes_of(&fog.uniform()));
+                queue.write_buffer(
+                    &material_buffer,
+                    0,
+                    bytemuck::bytes_of(&material_uniform(material, dispersion)),
+                );
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                queue.write_buffer(
+                    &sky_matrix_buffer,
+                    0,
+                    bytemuck::bytes_of(
+                        &camera.sky_inverse_view_projection(sc_desc.width, sc_desc.height),
+                    ),
+                );
+                queue.write_buffer(
+                    &directional_light_buffer,
+                    0,
+                    bytemuck::bytes_of(&direction_light(camera.eye())),
+                );
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &fog_bind_groups[current_sky], &[]);
+                    for i in 0..draw_count {
+                        let primitive = &scene.primitives[i % scene.primitives.len()];
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+
+                    rpass.set_pipeline(&sky_pipeline);
+                    rpass.set_bind_group(0, &sky_matrix_bind_group, &[]);
+                    rpass.set_bind_group(1, &sky_bind_groups[current_sky], &[]);
+                    rpass.draw(0..4, 0..1);
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    // Temporarily avoid srgb formats for the swapchain on the web
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .map(|(p, n)| Vertex {
+                            _pos: [p[0], p[1], p[2], 1.0],
+                            _normal: n,
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+}
+
+mod texture {
+    use std::{
+        borrow::Cow,
+        io::{BufReader, Cursor},
+        num::NonZeroU32,
+    };
+
+    use anyhow::*;
+    use image::GenericImageView;
+    use wgpu::util::DeviceExt;
+
+    // The texture is held to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        pub fn create_depth_texture(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            label: &str,
+        ) -> Self {
+            let size = wgpu::Extent3d {
+                // 2.
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            };
+            let desc = wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT // 3.
+                | wgpu::TextureUsage::SAMPLED,
+            };
+            let texture = device.create_texture(&desc);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                // 4.
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual), // 5.
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            });
+
+            Self {
+                _texture: texture,
+                view,
+                sampler,
+            }
+        }
+
+        // Six square sRGB images in the order +X, -X, +Y, -Y, +Z, -Z.
+        pub fn create_cube_from_faces(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            faces: &[image::DynamicImage],
+            label: &str,
+        ) -> Result<Self> {
+            if faces.len() != 6 {
+                bail!("A cube map needs 6 faces, got {}", faces.len());
+            }
+            let (face_size, _) = faces[0].dimensions();
+            if faces
+                .iter()
+                .any(|face| face.dimensions() != (face_size, face_size))
+            {
+                bail!("Cube map faces must be square and all the same size");
+            }
+
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: face_size,
+                    height: face_size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            });
+
+            for (layer, face) in faces.iter().enumerate() {
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                    },
+                    &face.to_rgba8(),
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(4 * face_size),
+                        rows_per_image: NonZeroU32::new(face_size),
+                    },
+                    wgpu::Extent3d {
+                        width: face_size,
+                        height: face_size,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+
+            Ok(Self::from_cube_texture(device, texture))
+        }
+
+        // A horizontal (4x3) or vertical (3x4) cross. Both have +Y above and -Y below +Z, with
+        // -X, +Z, +X in the middle row. The horizontal cross continues the row with -Z, the
+        // vertical one puts -Z upside down at the bottom.
+        pub fn create_cube_from_cross(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            img: &image::DynamicImage,
+            label: &str,
+        ) -> Result<Self> {
+            let (width, height) = img.dimensions();
+            let faces = if width * 3 == height * 4 {
+                let n = width / 4;
+                vec![
+                    img.crop_imm(2 * n, n, n, n),
+                    img.crop_imm(0, n, n, n),
+                    img.crop_imm(n, 0, n, n),
+                    img.crop_imm(n, 2 * n, n, n),
+                    img.crop_imm(n, n, n, n),
+                    img.crop_imm(3 * n, n, n, n),
+                ]
+            } else if width * 4 == height * 3 {
+                let n = width / 3;
+                vec![
+                    img.crop_imm(2 * n, n, n, n),
+                    img.crop_imm(0, n, n, n),
+                    img.crop_imm(n, 0, n, n),
+                    img.crop_imm(n, 2 * n, n, n),
+                    img.crop_imm(n, n, n, n),
+                    img.crop_imm(n, 3 * n, n, n).rotate180(),
+                ]
+            } else {
+                bail!(
+                    "{}x{} is neither a horizontal nor a vertical cross",
+                    width,
+                    height
+                );
+            };
+
+            Self::create_cube_from_faces(device, queue, &faces, label)
+        }
+
+        // Render the six faces from an equirectangular Radiance HDR image. The result keeps
+        // the full range in a half float cube map.
+        pub fn create_cube_from_equirectangular(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            face_size: u32,
+            label: &str,
+        ) -> Result<Self> {
+            let equirect = Self::from_radiance(device, queue, bytes, label)?;
+            let format = wgpu::TextureFormat::Rgba16Float;
+
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: face_size,
+                    height: face_size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("equirect_to_cube"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/equirect_to_cube.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("equirect_to_cube"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for face in 0..6 {
+                let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("face index"),
+                    contents: bytemuck::cast_slice(&[face, 0, 0, 0]),
+                    usage: wgpu::BufferUsage::UNIFORM,
+                });
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&equirect.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&equirect.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: face_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let face_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("cube face"),
+                    format: None,
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: wgpu::TextureAspect::All,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: face,
+                    array_layer_count: NonZeroU32::new(1),
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &face_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+
+            Ok(Self::from_cube_texture(device, texture))
+        }
+
+        fn from_cube_texture(device: &wgpu::Device, texture: wgpu::Texture) -> Self {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Self {
+                _texture: texture,
+                view,
+                sampler,
+            }
+        }
+
+        // RGBE only has 8 bits of mantissa, so half floats lose nothing.
+        fn from_radiance(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+        ) -> Result<Self> {
+            let decoder = image::codecs::hdr::HdrDecoder::new(BufReader::new(Cursor::new(bytes)))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .flat_map(|image::Rgb([r, g, b])| {
+                    [r, g, b, 1.0]
+                        .iter()
+                        .map(|&c| half::f16::from_f32(c).to_bits())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<u16>>();
+
+            let texture = device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: metadata.width,
+                        height: metadata.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                },
+                bytemuck::cast_slice(&pixels),
+            );
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Wrap horizontally across the longitude seam.
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+            })
+        }
+    }
+}
@@ -0,0 +1,917 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Quaternion};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<loader::DecodedImage>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let texture_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        });
+
+    let dummy_texture = texture::Texture::dummy(&device, &queue, "dummy").unwrap();
+
+    let dummy_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&dummy_texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&dummy_texture.sampler),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    });
+
+    // Pass a .gltf or .glb file as the first argument to view it instead of the teapot.
+    // Only the buffers are read here. `gltf::import` would also decode every image before
+    // returning, which is exactly what keeps large models from opening quickly.
+    let (document, buffers, base) = match std::env::args().nth(1) {
+        Some(path) => {
+            let path = std::path::PathBuf::from(path);
+            let gltf = gltf::Gltf::open(&path).unwrap();
+            let base = path.parent().map(|parent| parent.to_path_buf());
+            let buffers = loader::read_buffers(&gltf.document, base.as_deref(), gltf.blob).unwrap();
+            (gltf.document, buffers, base)
+        }
+        None => {
+            let gltf = gltf::Gltf::from_slice(include_bytes!("../assets/teapot.glb")).unwrap();
+            let buffers = loader::read_buffers(&gltf.document, None, gltf.blob).unwrap();
+            (gltf.document, buffers, None)
+        }
+    };
+
+    let mut scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Decode the textures on worker threads. Each one arrives as a user event, until then
+    // the primitives using it are drawn with the dummy texture.
+    let texture_count =
+        loader::stream_textures(&document, &buffers, base, event_loop.create_proxy());
+    let mut loaded_count = 0;
+    window.set_title(&format!("sample_10_05: 0/{} textures", texture_count));
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/10_05.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    let global_matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("global matrix"),
+        contents: bytemuck::cast_slice(&[Matrix {
+            _matrix: (Matrix4::from_translation(cgmath::vec3(0.0, 0.0, 0.5))
+                * Matrix4::from_scale(0.01)
+                * Matrix4::from(Quaternion::from(cgmath::Euler {
+                    x: cgmath::Rad(0.0),
+                    y: cgmath::Rad(0.0),
+                    z: cgmath::Rad(0.0),
+                })))
+            .into(),
+        }]),
+        usage: wgpu::BufferUsage::UNIFORM,
+    });
+
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: scene.primitives.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: global_matrix_buffer.as_entire_binding(),
+        }],
+        label: Some("uniform_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &texture_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress * 2,
+                shader_location: 2,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Front),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less, // 1.
+            stencil: wgpu::StencilState::default(),     // 2.
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut depth_texture =
+        texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                depth_texture =
+                    texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    for (i, primitive) in scene.primitives.iter().enumerate() {
+                        queue.write_buffer(
+                            &local_matrix_buffer,
+                            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+                            bytemuck::bytes_of(&Matrix {
+                                _matrix: primitive.transform.into(),
+                            }),
+                        );
+                    }
+
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_bind_group(0, &uniform_bind_group, &[]);
+                    for (i, primitive) in scene.primitives.iter().enumerate() {
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_bind_group(2, &dummy_bind_group, &[]);
+                        if let Some(id) = primitive.texture_id {
+                            if let Some(bind_group) = &scene.textures[id] {
+                                rpass.set_bind_group(2, bind_group, &[]);
+                            }
+                        }
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            // Uploading only queues a copy, so the next frame is not held up by it.
+            Event::UserEvent(decoded) => {
+                loaded_count += 1;
+                window.set_title(&format!(
+                    "sample_10_05: {}/{} textures",
+                    loaded_count, texture_count
+                ));
+
+                let image = match decoded.image {
+                    Ok(image) => image,
+                    Err(e) => {
+                        eprintln!(
+                            "Failed to load texture of material {}: {}",
+                            decoded.material, e
+                        );
+                        return;
+                    }
+                };
+
+                let diffuse_texture = texture::Texture::from_image(
+                    &device,
+                    &queue,
+                    &image,
+                    None,
+                    texture::TextureRole::Color,
+                )
+                .unwrap();
+
+                scene.textures[decoded.material] =
+                    Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: &texture_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                            },
+                        ],
+                        label: Some("diffuse_bind_group"),
+                    }));
+                window.request_redraw();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::with_user_event();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    // Temporarily avoid srgb formats for the swapchain on the web
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+    use wgpu::Device;
+    use winit::event_loop::EventLoopProxy;
+
+    // Decoding is CPU bound, so a handful of threads keeps the main thread free without
+    // oversubscribing the machine.
+    const WORKER_COUNT: usize = 4;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _color: [f32; 4],
+        _tex_coord: [f32; 2],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+        pub texture_id: Option<usize>,
+    }
+
+    pub struct Scene {
+        // One slot per material, filled in as the textures finish loading.
+        pub textures: Vec<Option<wgpu::BindGroup>>,
+        pub primitives: Vec<Primitive>,
+    }
+
+    // The base color image of a material, decoded on a worker thread.
+    pub struct DecodedImage {
+        pub material: usize,
+        pub image: anyhow::Result<image::DynamicImage>,
+    }
+
+    enum EncodedImage {
+        Bytes(Vec<u8>),
+        File(PathBuf),
+    }
+
+    impl EncodedImage {
+        fn decode(self) -> anyhow::Result<image::DynamicImage> {
+            let bytes = match self {
+                EncodedImage::Bytes(bytes) => bytes,
+                EncodedImage::File(path) => std::fs::read(path)?,
+            };
+            Ok(image::load_from_memory(&bytes)?)
+        }
+    }
+
+    // Read every buffer of the document, from the binary chunk of a .glb or from files next to
+    // the .gltf. Like the images, buffers embedded as data URIs are not supported.
+    pub fn read_buffers(
+        root: &gltf::Document,
+        base: Option<&std::path::Path>,
+        mut blob: Option<Vec<u8>>,
+    ) -> anyhow::Result<Vec<gltf::buffer::Data>> {
+        root.buffers()
+            .map(|buffer| -> anyhow::Result<gltf::buffer::Data> {
+                let mut data = match buffer.source() {
+                    gltf::buffer::Source::Bin => blob
+                        .take()
+                        .ok_or_else(|| anyhow::anyhow!("Missing binary chunk"))?,
+                    gltf::buffer::Source::Uri(uri) => {
+                        let path = base.unwrap_or_else(|| std::path::Path::new("")).join(uri);
+                        std::fs::read(&path).map_err(|e| {
+                            anyhow::anyhow!("Failed to read {}: {}", path.display(), e)
+                        })?
+                    }
+                };
+                if data.len() < buffer.length() {
+                    anyhow::bail!("Buffer {} is truncated", buffer.index());
+                }
+                // The binary chunk is padded to a multiple of four bytes.
+                data.truncate(buffer.length());
+                Ok(gltf::buffer::Data(data))
+            })
+            .collect()
+    }
+
+    // Queue the base color texture of every material for decoding and return how many there
+    // are. The images are copied out of `buffers` so that the workers own their input.
+    pub fn stream_textures(
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        base: Option<PathBuf>,
+        proxy: EventLoopProxy<DecodedImage>,
+    ) -> usize {
+        let jobs = root
+            .materials()
+            .filter_map(|material| {
+                let info = material.pbr_metallic_roughness().base_color_texture()?;
+                let encoded = match info.texture().source().source() {
+                    gltf::image::Source::View { view, mime_type: _ } => {
+                        let parent_buffer_data = &buffers[view.buffer().index()].0;
+                        let begin = view.offset();
+                        let end = begin + view.length();
+                        EncodedImage::Bytes(parent_buffer_data[begin..end].to_vec())
+                    }
+                    gltf::image::Source::Uri { uri, mime_type: _ } => {
+                        EncodedImage::File(base.clone().unwrap_or_default().join(uri))
+                    }
+                };
+                Some((material.index()?, encoded))
+            })
+            .collect::<Vec<_>>();
+
+        let count = jobs.len();
+        let jobs = Arc::new(Mutex::new(jobs.into_iter()));
+        for _ in 0..WORKER_COUNT.min(count) {
+            let jobs = jobs.clone();
+            let proxy = proxy.clone();
+            std::thread::spawn(move || loop {
+                // Hold the lock only while taking the next job, not while decoding it.
+                let job = jobs.lock().unwrap().next();
+                let (material, encoded) = match job {
+                    Some(job) => job,
+                    None => break,
+                };
+                let image = encoded.decode();
+                // The event loop is gone once the window closes, and so is the interest in
+                // the remaining textures.
+                if proxy.send_event(DecodedImage { material, image }).is_err() {
+                    break;
+                }
+            });
+        }
+
+        count
+    }
+
+    // Build the vertex and index buffers. Textures are left empty for `stream_textures`.
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let textures = root.materials().map(|_| None).collect();
+
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let material = primitive.material();
+                    let color = material.pbr_metallic_roughness().base_color_factor();
+
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = if let Some(coords) = reader.read_tex_coords(0) {
+                        reader
+                            .read_positions()
+                            .unwrap()
+                            .zip(coords.into_f32())
+                            .map(|(p, c)| Vertex {
+                                _pos: [p[0], p[1], p[2], 1.0],
+                                _color: [0.0, 0.0, 0.0, 0.0],
+                                _tex_coord: c,
+                            })
+                            .collect::<Vec<_>>()
+                    } else {
+                        reader
+                            .read_positions()
+                            .unwrap()
+                            .map(|p| Vertex {
+                                _pos: [p[0], p[1], p[2], 1.0],
+                                _color: color,
+                                _tex_coord: [0.0, 0.0],
+                            })
+                            .collect::<Vec<_>>()
+                    };
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                        texture_id: material.index(),
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene {
+            primitives,
+            textures,
+        }
+    }
+}
+
+mod texture {
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
+
+    use anyhow::*;
+    use image::GenericImageView;
+
+    // The texture is held to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Base color authored in sRGB. Sampling decodes it to linear values.
+        Color,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            }
+        }
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        pub fn create_depth_texture(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            label: &str,
+        ) -> Self {
+            let size = wgpu::Extent3d {
+                // 2.
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            };
+            let desc = wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT // 3.
+                | wgpu::TextureUsage::SAMPLED,
+            };
+            let texture = device.create_texture(&desc);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                // 4.
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual), // 5.
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            });
+
+            Self {
+                _texture: texture,
+                view,
+                sampler,
+            }
+        }
+
+        pub fn dummy(device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Self> {
+            let img = image::DynamicImage::new_rgba8(16, 16);
+            Self::from_image(device, queue, &img, Some(label), TextureRole::Color)
+        }
+
+        pub fn from_image(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            img: &image::DynamicImage,
+            label: Option<&str>,
+            role: TextureRole,
+        ) -> Result<Self> {
+            let rgba = img.to_rgba8().to_vec();
+            let dimensions = img.dimensions();
+
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    rows_per_image: NonZeroU32::new(dimensions.1),
+                },
+                size,
+            );
+
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
+                ..Default::default()
+            });
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+            })
+        }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
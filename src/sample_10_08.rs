@@ -0,0 +1,915 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DirectionLight {
+    _eye_position: [f32; 3],
+    _pad0: f32,
+    _directional_light_direction: [f32; 3],
+    _pad1: f32,
+    _directianal_light_color: [f32; 3],
+    _pad2: f32,
+    _ambient_color: [f32; 3],
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const EYE_POSITION: [f32; 3] = [1.5, 1.0, 2.5];
+
+fn view_projection(width: u32, height: u32) -> Matrix {
+    let projection = cgmath::perspective(
+        cgmath::Deg(60.0),
+        width as f32 / height.max(1) as f32,
+        0.1,
+        100.0,
+    );
+    let view = Matrix4::look_at_rh(
+        Point3::from(EYE_POSITION),
+        Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+    );
+    Matrix {
+        _matrix: (OPENGL_TO_WGPU_MATRIX * projection * view).into(),
+    }
+}
+
+fn create_blit_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    target: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&target.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&target.sampler),
+            },
+        ],
+        label: Some("blit_bind_group"),
+    })
+}
+
+// The scene is rendered offscreen so that the targets can be copied, as swap chain frames
+// cannot be read back.
+fn create_targets(
+    device: &wgpu::Device,
+    sc_desc: &wgpu::SwapChainDescriptor,
+) -> (texture::Texture, texture::Texture) {
+    let size = wgpu::Extent3d {
+        width: sc_desc.width,
+        height: sc_desc.height,
+        depth_or_array_layers: 1,
+    };
+    let color = texture::Texture::create_render_target(
+        device,
+        size,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        1,
+        wgpu::TextureUsage::RENDER_ATTACHMENT
+            | wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_SRC,
+    );
+    let depth = texture::Texture::create_render_target(
+        device,
+        size,
+        texture::Texture::DEPTH_FORMAT,
+        1,
+        wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+    );
+    (color, depth)
+}
+
+// Reads a target back and writes it to the working directory.
+fn save_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &texture::Texture,
+    path: &str,
+) -> anyhow::Result<()> {
+    readback::read_texture(device, queue, texture)?.save(path)?;
+    println!("Saved {}", path);
+    Ok(())
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let teapot = include_bytes!("../assets/teapot.glb");
+    let (document, buffers, _images) = gltf::import_slice(teapot).unwrap();
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/10_08.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let blit_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("blit"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/blit.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    window.set_title("sample_10_08: P saves a PNG, E an EXR, R raw bytes and D the depth buffer");
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    // The teapot is authored in centimeters.
+    let model =
+        Matrix4::from_translation(cgmath::vec3(0.0, -0.25, 0.0)) * Matrix4::from_scale(0.01);
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: scene.primitives.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, primitive) in scene.primitives.iter().enumerate() {
+        queue.write_buffer(
+            &local_matrix_buffer,
+            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(&Matrix {
+                _matrix: (model * primitive.transform).into(),
+            }),
+        );
+    }
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let directional_light_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("light"),
+        contents: bytemuck::cast_slice(&[DirectionLight {
+            _eye_position: EYE_POSITION,
+            _pad0: 0.0,
+            _directional_light_direction: cgmath::InnerSpace::normalize(cgmath::vec3(
+                1.0f32, -1.0, 1.0,
+            ))
+            .into(),
+            _pad1: 0.0,
+            _directianal_light_color: [0.5, 0.5, 0.5],
+            _pad2: 0.0,
+            _ambient_color: [0.3, 0.3, 0.3],
+        }]),
+        usage: wgpu::BufferUsage::UNIFORM,
+    });
+
+    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &directional_light_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &directional_light_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &directional_light_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::TextureFormat::Rgba8UnormSrgb.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("blit"),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &blit_shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &blit_shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+    });
+    let blit_bind_group_layout = blit_pipeline.get_bind_group_layout(0);
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let (mut color_target, mut depth_target) = create_targets(&device, &sc_desc);
+    let mut blit_bind_group =
+        create_blit_bind_group(&device, &blit_bind_group_layout, &color_target);
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &blit_shader, &pipeline_layout);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                let (color, depth) = create_targets(&device, &sc_desc);
+                color_target = color;
+                depth_target = depth;
+                blit_bind_group =
+                    create_blit_bind_group(&device, &blit_bind_group_layout, &color_target);
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&view_projection(sc_desc.width, sc_desc.height)),
+                );
+            }
+            // The targets still hold the last frame, so there is no need to redraw first.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let (texture, path) = match keycode {
+                    VirtualKeyCode::P => (&color_target, "sample_10_08.png"),
+                    VirtualKeyCode::E => (&color_target, "sample_10_08.exr"),
+                    VirtualKeyCode::R => (&color_target, "sample_10_08.raw"),
+                    VirtualKeyCode::D => (&depth_target, "sample_10_08_depth.png"),
+                    _ => return,
+                };
+                if let Err(err) = save_texture(&device, &queue, texture, path) {
+                    eprintln!("Failed to save {}: {:?}", path, err);
+                }
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("scene"),
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &color_target.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_target.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, &directional_light_bind_group, &[]);
+                    for (i, primitive) in scene.primitives.iter().enumerate() {
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("blit"),
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    rpass.set_pipeline(&blit_pipeline);
+                    rpass.set_bind_group(0, &blit_bind_group, &[]);
+                    rpass.draw(0..4, 0..1);
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .map(|(p, n)| Vertex {
+                            _pos: [p[0], p[1], p[2], 1.0],
+                            _normal: n,
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+}
+
+mod texture {
+    pub struct Texture {
+        pub texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+        pub format: wgpu::TextureFormat,
+        pub size: wgpu::Extent3d,
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        // A texture to render into instead of the swap chain. Add `COPY_SRC` to the usage to
+        // read it back.
+        pub fn create_render_target(
+            device: &wgpu::Device,
+            size: wgpu::Extent3d,
+            format: wgpu::TextureFormat,
+            sample_count: u32,
+            usage: wgpu::TextureUsage,
+        ) -> Self {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("render target"),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+            });
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+                format,
+                size,
+            }
+        }
+    }
+}
+
+mod readback {
+    use super::texture::Texture;
+    use anyhow::*;
+    use std::{num::NonZeroU32, path::Path};
+
+    // Pixels copied back from the GPU, with the row padding removed.
+    pub struct Image {
+        pub width: u32,
+        pub height: u32,
+        pub format: wgpu::TextureFormat,
+        pub data: Vec<u8>,
+    }
+
+    // Copies the first mip level of a texture into a mappable buffer and waits for it. The
+    // texture needs `COPY_SRC` usage.
+    pub fn read_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &Texture,
+    ) -> Result<Image> {
+        let info = texture.format.describe();
+        if info.block_dimensions != (1, 1) {
+            bail!("Cannot read back compressed {:?} textures", texture.format);
+        }
+
+        // Buffer rows have to be a multiple of 256 bytes, so they are padded and the padding
+        // is dropped again after mapping.
+        let width = texture.size.width;
+        let height = texture.size.height;
+        let unpadded_bytes_per_row = width * info.block_size as u32;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).map_err(|_| anyhow!("Failed to map the readback buffer"))?;
+
+        let data = slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+            .flat_map(|row| row[..unpadded_bytes_per_row as usize].iter().copied())
+            .collect();
+        buffer.unmap();
+
+        Ok(Image {
+            width,
+            height,
+            format: texture.format,
+            data,
+        })
+    }
+
+    fn srgb_to_linear(c: f32) -> f32 {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(c: f32) -> f32 {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    fn f32_at(bytes: &[u8], i: usize) -> f32 {
+        f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+    }
+
+    fn f16_at(bytes: &[u8], i: usize) -> f32 {
+        half::f16::from_le_bytes([bytes[i], bytes[i + 1]]).to_f32()
+    }
+
+    impl Image {
+        // Linear RGBA values. 8-bit sRGB formats are decoded, and depth is repeated in RGB.
+        pub fn to_linear(&self) -> Result<Vec<[f32; 4]>> {
+            use wgpu::TextureFormat::*;
+            let unorm = |b: u8| b as f32 / 255.0;
+            let srgb = |b: u8| srgb_to_linear(b as f32 / 255.0);
+            let pixels = match self.format {
+                Rgba8Unorm => self
+                    .data
+                    .chunks(4)
+                    .map(|p| [unorm(p[0]), unorm(p[1]), unorm(p[2]), unorm(p[3])])
+                    .collect(),
+                Rgba8UnormSrgb => self
+                    .data
+                    .chunks(4)
+                    .map(|p| [srgb(p[0]), srgb(p[1]), srgb(p[2]), unorm(p[3])])
+                    .collect(),
+                Bgra8Unorm => self
+                    .data
+                    .chunks(4)
+                    .map(|p| [unorm(p[2]), unorm(p[1]), unorm(p[0]), unorm(p[3])])
+                    .collect(),
+                Bgra8UnormSrgb => self
+                    .data
+                    .chunks(4)
+                    .map(|p| [srgb(p[2]), srgb(p[1]), srgb(p[0]), unorm(p[3])])
+                    .collect(),
+                R8Unorm => self
+                    .data
+                    .iter()
+                    .map(|&r| [unorm(r), unorm(r), unorm(r), 1.0])
+                    .collect(),
+                Rg8Unorm => self
+                    .data
+                    .chunks(2)
+                    .map(|p| [unorm(p[0]), unorm(p[1]), 0.0, 1.0])
+                    .collect(),
+                Rgba16Float => (0..self.data.len())
+                    .step_by(8)
+                    .map(|i| {
+                        [
+                            f16_at(&self.data, i),
+                            f16_at(&self.data, i + 2),
+                            f16_at(&self.data, i + 4),
+                            f16_at(&self.data, i + 6),
+                        ]
+                    })
+                    .collect(),
+                Rgba32Float => (0..self.data.len())
+                    .step_by(16)
+                    .map(|i| {
+                        [
+                            f32_at(&self.data, i),
+                            f32_at(&self.data, i + 4),
+                            f32_at(&self.data, i + 8),
+                            f32_at(&self.data, i + 12),
+                        ]
+                    })
+                    .collect(),
+                R32Float | Depth32Float => (0..self.data.len())
+                    .step_by(4)
+                    .map(|i| {
+                        let d = f32_at(&self.data, i);
+                        [d, d, d, 1.0]
+                    })
+                    .collect(),
+                format => bail!("Reading back {:?} is not supported", format),
+            };
+            Ok(pixels)
+        }
+
+        // An 8-bit sRGB image for viewing. Float colors are clamped, and depth is stretched
+        // over the range of the covered pixels because most of it sits close to 1.
+        pub fn to_rgba8(&self) -> Result<image::RgbaImage> {
+            let pixels = self.to_linear()?;
+            let encode = |c: f32| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+            let bytes = match self.format {
+                wgpu::TextureFormat::Depth32Float => {
+                    let near = pixels.iter().map(|p| p[0]).fold(1.0, f32::min);
+                    let range = (1.0 - near).max(f32::EPSILON);
+                    pixels
+                        .iter()
+                        .flat_map(|p| {
+                            let gray = (((p[0] - near) / range) * 255.0).round() as u8;
+                            vec![gray, gray, gray, 255]
+                        })
+                        .collect()
+                }
+                // Data formats are stored as is.
+                wgpu::TextureFormat::Rgba8Unorm
+                | wgpu::TextureFormat::Bgra8Unorm
+                | wgpu::TextureFormat::R8Unorm
+                | wgpu::TextureFormat::Rg8Unorm => pixels
+                    .iter()
+                    .flat_map(|p| {
+                        p.iter()
+                            .map(|c| (c * 255.0).round() as u8)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect(),
+                _ => pixels
+                    .iter()
+                    .flat_map(|p| {
+                        vec![
+                            encode(p[0]),
+                            encode(p[1]),
+                            encode(p[2]),
+                            (p[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+                        ]
+                    })
+                    .collect(),
+            };
+            image::RgbaImage::from_raw(self.width, self.height, bytes)
+                .ok_or_else(|| anyhow!("Pixel data does not match the image size"))
+        }
+
+        // The file type follows the extension. `.exr` keeps linear float values, `.raw` writes
+        // the texel bytes unchanged and anything else is handed to the image crate.
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+            let path = path.as_ref();
+            let extension = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_ascii_lowercase());
+            match extension.as_deref() {
+                Some("exr") => {
+                    let pixels = self.to_linear()?;
+                    let width = self.width as usize;
+                    exr::prelude::write_rgba_file(path, width, self.height as usize, |x, y| {
+                        let p = pixels[y * width + x];
+                        (p[0], p[1], p[2], p[3])
+                    })?;
+                }
+                Some("raw") => std::fs::write(path, &self.data)?,
+                _ => self.to_rgba8()?.save(path)?,
+            }
+            Ok(())
+        }
+    }
+}
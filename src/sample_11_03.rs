@@ -0,0 +1,1148 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, Transform, Vector3};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DirectionLight {
+    _eye_position: [f32; 3],
+    _pad0: f32,
+    _directional_light_direction: [f32; 3],
+    _pad1: f32,
+    _directianal_light_color: [f32; 3],
+    _pad2: f32,
+    _ambient_color: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct CascadeUniform {
+    _light_view_projection: [[[f32; 4]; 4]; MAX_CASCADES],
+    _splits: [f32; MAX_CASCADES],
+    _depth_bias: [f32; MAX_CASCADES],
+    _normal_bias: [f32; MAX_CASCADES],
+    _camera_forward: [f32; 3],
+    _count: u32,
+    _blend: f32,
+    _texel_size: f32,
+    _debug: u32,
+    _pad: f32,
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const MAX_CASCADES: usize = 4;
+const SHADOW_MAP_SIZE: u32 = 2048;
+// Shadows end this far from the camera, which is much closer than the far plane.
+const SHADOW_DISTANCE: f32 = 40.0;
+// The biases are given in world units and converted for each cascade, whose texels cover
+// more of the world the further away they are.
+const DEPTH_BIAS: f32 = 0.05;
+const NORMAL_BIAS_TEXELS: f32 = 1.5;
+
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl Camera {
+    const FOV_Y: cgmath::Deg<f32> = cgmath::Deg(60.0);
+    const NEAR: f32 = 0.1;
+
+    fn eye(&self) -> Point3<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Point3::new(
+            self.distance * pitch.cos() * yaw.sin(),
+            self.distance * pitch.sin(),
+            self.distance * pitch.cos() * yaw.cos(),
+        )
+    }
+
+    fn forward(&self) -> Vector3<f32> {
+        (Point3::new(0.0, 0.0, 0.0) - self.eye()).normalize()
+    }
+
+    fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(
+            self.eye(),
+            Point3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_y(),
+        )
+    }
+
+    fn view_projection(&self, width: u32, height: u32) -> Matrix {
+        let projection = OPENGL_TO_WGPU_MATRIX
+            * cgmath::perspective(
+                Self::FOV_Y,
+                width as f32 / height.max(1) as f32,
+                Self::NEAR,
+                100.0,
+            );
+        Matrix {
+            _matrix: (projection * self.view()).into(),
+        }
+    }
+
+    // The corners of the part of the view frustum between two view depths.
+    fn frustum_corners(&self, aspect: f32, near: f32, far: f32) -> [Point3<f32>; 8] {
+        let forward = self.forward();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let tan_y = (cgmath::Rad::from(Self::FOV_Y).0 / 2.0).tan();
+        let tan_x = tan_y * aspect;
+        let eye = self.eye();
+        let mut corners = [eye; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let depth = if i & 4 == 0 { near } else { far };
+            let x = if i & 1 == 0 { -tan_x } else { tan_x };
+            let y = if i & 2 == 0 { -tan_y } else { tan_y };
+            *corner = eye + (forward + right * x + up * y) * depth;
+        }
+        corners
+    }
+}
+
+fn light_direction() -> Vector3<f32> {
+    Vector3::new(1.0, -1.5, 0.6).normalize()
+}
+
+fn direction_light(eye: Point3<f32>) -> DirectionLight {
+    DirectionLight {
+        _eye_position: eye.into(),
+        _pad0: 0.0,
+        _directional_light_direction: light_direction().into(),
+        _pad1: 0.0,
+        _directianal_light_color: [0.7, 0.7, 0.7],
+        _pad2: 0.0,
+        _ambient_color: [0.15, 0.15, 0.15],
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CascadeSettings {
+    count: usize,
+    // 0 splits the shadow distance evenly, 1 logarithmically, which matches how perspective
+    // shrinks things but leaves the last cascades very long.
+    lambda: f32,
+    // The fraction at the end of each cascade that blends into the next one.
+    blend: f32,
+    debug: bool,
+}
+
+// The view depth where each cascade ends.
+fn cascade_splits(settings: &CascadeSettings) -> Vec<f32> {
+    let (near, far) = (Camera::NEAR, SHADOW_DISTANCE);
+    (1..=settings.count)
+        .map(|i| {
+            let p = i as f32 / settings.count as f32;
+            let log = near * (far / near).powf(p);
+            let linear = near + (far - near) * p;
+            settings.lambda * log + (1.0 - settings.lambda) * linear
+        })
+        .collect()
+}
+
+// Fits an orthographic light frustum around each slice of the camera frustum. The frustum is
+// sized by the slice's bounding sphere and moved in whole texels, so it doesn't change as the
+// camera turns and shadow edges don't shimmer. It reaches back to the scene bounds so casters
+// outside the slice still land in the map.
+fn cascade_uniform(
+    camera: &Camera,
+    aspect: f32,
+    settings: &CascadeSettings,
+    scene_corners: &[Point3<f32>],
+) -> CascadeUniform {
+    let direction = light_direction();
+    let rotation = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), direction, Vector3::unit_y());
+    let scene_near = scene_corners
+        .iter()
+        .map(|p| rotation.transform_point(*p).z)
+        .fold(f32::MIN, f32::max);
+
+    let splits = cascade_splits(settings);
+    let mut uniform = CascadeUniform {
+        _light_view_projection: [[[0.0; 4]; 4]; MAX_CASCADES],
+        _splits: [0.0; MAX_CASCADES],
+        _depth_bias: [0.0; MAX_CASCADES],
+        _normal_bias: [0.0; MAX_CASCADES],
+        _camera_forward: camera.forward().into(),
+        _count: settings.count as u32,
+        _blend: settings.blend,
+        _texel_size: 1.0 / SHADOW_MAP_SIZE as f32,
+        _debug: settings.debug as u32,
+        _pad: 0.0,
+    };
+    for (i, &end) in splits.iter().enumerate() {
+        let start = if i == 0 { Camera::NEAR } else { splits[i - 1] };
+        let corners = camera.frustum_corners(aspect, start, end);
+        let center = Point3::centroid(&corners);
+        let radius = corners
+            .iter()
+            .map(|corner| corner.distance(center))
+            .fold(0.0, f32::max);
+        // Rounding keeps the size from changing with floating point noise.
+        let radius = (radius * 16.0).ceil() / 16.0;
+        let texel = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+
+        let center = rotation.transform_point(center);
+        let x = (center.x / texel).floor() * texel;
+        let y = (center.y / texel).floor() * texel;
+        let near = -(center.z + radius).max(scene_near);
+        let far = -(center.z - radius);
+        let projection = cgmath::ortho(x - radius, x + radius, y - radius, y + radius, near, far);
+
+        uniform._light_view_projection[i] = (OPENGL_TO_WGPU_MATRIX * projection * rotation).into();
+        uniform._splits[i] = end;
+        uniform._depth_bias[i] = DEPTH_BIAS / (far - near);
+        uniform._normal_bias[i] = NORMAL_BIAS_TEXELS * texel;
+    }
+    uniform
+}
+
+// The eight corners of a glTF bounding box after `transform`.
+fn box_corners(bounds: &gltf::mesh::BoundingBox, transform: Matrix4<f32>) -> Vec<Point3<f32>> {
+    (0..8)
+        .map(|i| {
+            let corner = Point3::new(
+                if i & 1 == 0 {
+                    bounds.min[0]
+                } else {
+                    bounds.max[0]
+                },
+                if i & 2 == 0 {
+                    bounds.min[1]
+                } else {
+                    bounds.max[1]
+                },
+                if i & 4 == 0 {
+                    bounds.min[2]
+                } else {
+                    bounds.max[2]
+                },
+            );
+            transform.transform_point(corner)
+        })
+        .collect()
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+// Uploads the cascades for the lighting shader, and each light matrix on its own for the
+// shadow passes.
+fn write_cascades(
+    queue: &wgpu::Queue,
+    uniform_buffer: &wgpu::Buffer,
+    matrix_buffer: &wgpu::Buffer,
+    uniform: &CascadeUniform,
+) {
+    queue.write_buffer(uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    for (cascade, matrix) in uniform._light_view_projection.iter().enumerate() {
+        queue.write_buffer(
+            matrix_buffer,
+            cascade as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(matrix),
+        );
+    }
+}
+
+fn cascade_title(settings: &CascadeSettings) -> String {
+    format!(
+        "sample_11_03: {} cascades, split lambda {:.1}{}",
+        settings.count,
+        settings.lambda,
+        if settings.debug { ", debug view" } else { "" }
+    )
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    // Pass the path of a glTF file to shade it instead of a field of teapots.
+    let path = std::env::args().nth(1);
+    let (document, buffers, _images) = match &path {
+        Some(path) => gltf::import(path).unwrap(),
+        None => gltf::import_slice(include_bytes!("../assets/teapot.glb")).unwrap(),
+    };
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/11_03.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let shadow_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("shadow"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/shadow.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    // The teapot is authored in centimeters and repeated on a grid large enough to need more
+    // than one shadow map. Everything stands on a ground plane that catches the shadows.
+    let instances = if path.is_some() {
+        vec![Matrix4::from_scale(1.0)]
+    } else {
+        let model = Matrix4::from_scale(0.01);
+        (-4..=4)
+            .flat_map(|x| (-4..=4).map(move |z| (x, z)))
+            .map(|(x, z)| {
+                Matrix4::from_translation(cgmath::vec3(x as f32 * 2.0, 0.0, z as f32 * 2.0)) * model
+            })
+            .collect()
+    };
+    let mut primitives = scene.primitives;
+    let mut objects = instances
+        .iter()
+        .flat_map(|instance| {
+            primitives
+                .iter()
+                .enumerate()
+                .map(move |(index, primitive)| (index, instance * primitive.transform))
+        })
+        .collect::<Vec<_>>();
+    let corners = objects
+        .iter()
+        .flat_map(|(index, transform)| box_corners(&primitives[*index].bounds, *transform))
+        .collect::<Vec<_>>();
+    let ground = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+    let extent = corners
+        .iter()
+        .map(|p| p.x.abs().max(p.z.abs()))
+        .fold(0.0, f32::max);
+    objects.push((
+        primitives.len(),
+        Matrix4::from_translation(cgmath::vec3(0.0, ground, 0.0)),
+    ));
+    primitives.push(loader::create_plane(&device, extent + 2.0));
+    let scene_corners = objects
+        .iter()
+        .flat_map(|(index, transform)| box_corners(&primitives[*index].bounds, *transform))
+        .collect::<Vec<_>>();
+
+    // The arrow keys orbit the camera and Page Up and Page Down move it closer or further.
+    let mut camera = Camera {
+        yaw: 30.0,
+        pitch: 15.0,
+        distance: (extent * 0.75).max(3.0),
+    };
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&camera.view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: objects.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, (_, transform)) in objects.iter().enumerate() {
+        queue.write_buffer(
+            &local_matrix_buffer,
+            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(&Matrix {
+                _matrix: (*transform).into(),
+            }),
+        );
+    }
+
+    // 1-4 set the number of cascades, [ and ] move the splits between even and logarithmic,
+    // and V colors every pixel by its cascade.
+    let mut settings = CascadeSettings {
+        count: 3,
+        lambda: 0.7,
+        blend: 0.1,
+        debug: false,
+    };
+    window.set_title(&cascade_title(&settings));
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let directional_light_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let shadow_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: true,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("shadow_bind_group_layout"),
+        });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let directional_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("light"),
+        contents: bytemuck::bytes_of(&direction_light(camera.eye())),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let directional_light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &directional_light_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &directional_light_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<DirectionLight>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    // Each cascade is rendered with its light matrix in place of the camera's, so they share
+    // the layout of the view projection bind group.
+    let shadow_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("cascades"),
+        size: size_of::<CascadeUniform>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let cascade_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("cascade matrices"),
+        size: MAX_CASCADES as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let cascade_bind_groups = (0..MAX_CASCADES)
+        .map(|cascade| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &cascade_matrix_buffer,
+                        offset: cascade as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+                        size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+                    }),
+                }],
+                label: Some("cascade_bind_group"),
+            })
+        })
+        .collect::<Vec<_>>();
+    write_cascades(
+        &queue,
+        &shadow_buffer,
+        &cascade_matrix_buffer,
+        &cascade_uniform(
+            &camera,
+            size.width as f32 / size.height.max(1) as f32,
+            &settings,
+            &scene_corners,
+        ),
+    );
+
+    let shadow_map = texture::Texture::create_shadow_array(
+        &device,
+        SHADOW_MAP_SIZE,
+        MAX_CASCADES as u32,
+        "cascades",
+    );
+    let cascade_views = (0..MAX_CASCADES as u32)
+        .map(|cascade| shadow_map.layer_view(cascade))
+        .collect::<Vec<_>>();
+    let shadow_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &shadow_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: shadow_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+            },
+        ],
+        label: Some("shadow_bind_group"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &directional_light_bind_group_layout,
+            &shadow_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+    let shadow_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("shadow"),
+        bind_group_layouts: &[&uniform_bind_group_layout, &local_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    // Only depth is written, so there is no fragment stage. Both faces are drawn because the
+    // ground plane is seen from below when the light is low.
+    let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("shadow"),
+        layout: Some(&shadow_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shadow_shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: vertex_size as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 0,
+                }],
+            }],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+    let mut depth_texture = texture::Texture::create_depth_texture(&device, &sc_desc, "depth");
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &shadow_shader,
+            &pipeline_layout,
+            &shadow_pipeline_layout,
+        );
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                depth_texture = texture::Texture::create_depth_texture(&device, &sc_desc, "depth");
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                write_cascades(
+                    &queue,
+                    &shadow_buffer,
+                    &cascade_matrix_buffer,
+                    &cascade_uniform(
+                        &camera,
+                        sc_desc.width as f32 / sc_desc.height as f32,
+                        &settings,
+                        &scene_corners,
+                    ),
+                );
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::Left => camera.yaw -= 5.0,
+                    VirtualKeyCode::Right => camera.yaw += 5.0,
+                    VirtualKeyCode::Up => camera.pitch = (camera.pitch + 5.0).min(85.0),
+                    VirtualKeyCode::Down => camera.pitch = (camera.pitch - 5.0).max(5.0),
+                    VirtualKeyCode::PageUp => camera.distance = (camera.distance * 0.8).max(1.0),
+                    VirtualKeyCode::PageDown => camera.distance *= 1.25,
+                    VirtualKeyCode::Key1 => settings.count = 1,
+                    VirtualKeyCode::Key2 => settings.count = 2,
+                    VirtualKeyCode::Key3 => settings.count = 3,
+                    VirtualKeyCode::Key4 => settings.count = 4,
+                    VirtualKeyCode::LBracket => settings.lambda = (settings.lambda - 0.1).max(0.0),
+                    VirtualKeyCode::RBracket => settings.lambda = (settings.lambda + 0.1).min(1.0),
+                    VirtualKeyCode::V => settings.debug = !settings.debug,
+                    _ => return,
+                }
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                queue.write_buffer(
+                    &directional_light_buffer,
+                    0,
+                    bytemuck::bytes_of(&direction_light(camera.eye())),
+                );
+                write_cascades(
+                    &queue,
+                    &shadow_buffer,
+                    &cascade_matrix_buffer,
+                    &cascade_uniform(
+                        &camera,
+                        sc_desc.width as f32 / sc_desc.height as f32,
+                        &settings,
+                        &scene_corners,
+                    ),
+                );
+                window.set_title(&cascade_title(&settings));
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                for (cascade_view, cascade_bind_group) in cascade_views
+                    .iter()
+                    .zip(&cascade_bind_groups)
+                    .take(settings.count)
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("shadow"),
+                        color_attachments: &[],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: cascade_view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&shadow_pipeline);
+                    rpass.set_bind_group(0, cascade_bind_group, &[]);
+                    for (i, (index, _)) in objects.iter().enumerate() {
+                        let primitive = &primitives[*index];
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color {
+                                    r: 0.4,
+                                    g: 0.6,
+                                    b: 0.9,
+                                    a: 1.0,
+                                }),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, &directional_light_bind_group, &[]);
+                    rpass.set_bind_group(3, &shadow_bind_group, &[]);
+                    for (i, (index, _)) in objects.iter().enumerate() {
+                        let primitive = &primitives[*index];
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub bounds: gltf::mesh::BoundingBox,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .map(|(p, n)| Vertex {
+                            _pos: [p[0], p[1], p[2], 1.0],
+                            _normal: n,
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        bounds: primitive.bounding_box(),
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+
+    // A square in the XZ plane facing up, `half_size` from the center to each edge.
+    pub fn create_plane(device: &Device, half_size: f32) -> Primitive {
+        let vertices = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+            .iter()
+            .map(|[x, z]| Vertex {
+                _pos: [x * half_size, 0.0, z * half_size, 1.0],
+                _normal: [0.0, 1.0, 0.0],
+            })
+            .collect::<Vec<_>>();
+        let indices: [u32; 6] = [0, 2, 1, 0, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        Primitive {
+            transform: Matrix4::identity(),
+            bounds: gltf::mesh::BoundingBox {
+                min: [-half_size, 0.0, -half_size],
+                max: [half_size, 0.0, half_size],
+            },
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+mod texture {
+    pub struct Texture {
+        pub texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        pub fn create_depth_texture(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            label: &str,
+        ) -> Self {
+            let size = wgpu::Extent3d {
+                // 2.
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            };
+            let desc = wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT // 3.
+                | wgpu::TextureUsage::SAMPLED,
+            };
+            let texture = device.create_texture(&desc);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                // 4.
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual), // 5.
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+            }
+        }
+
+        // One square depth layer per cascade. Each layer is rendered through its own view, see
+        // `layer_view`, and all of them are sampled through `view`.
+        pub fn create_shadow_array(
+            device: &wgpu::Device,
+            size: u32,
+            layers: u32,
+            label: &str,
+        ) -> Self {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: layers,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            });
+
+            Self {
+                texture,
+                view,
+                sampler,
+            }
+        }
+
+        // A view of a single layer to render into.
+        pub fn layer_view(&self, layer: u32) -> wgpu::TextureView {
+            self.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("shadow layer"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            })
+        }
+    }
+}
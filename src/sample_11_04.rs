@@ -0,0 +1,868 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, Transform};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const MAX_LIGHTS: usize = 1024;
+const LIGHT_STEP: usize = 64;
+
+struct Camera {
+    eye: Point3<f32>,
+    near: f32,
+    far: f32,
+}
+
+impl Camera {
+    fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(
+            self.eye,
+            Point3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_y(),
+        )
+    }
+
+    fn projection(&self, width: u32, height: u32) -> Matrix4<f32> {
+        cgmath::perspective(
+            cgmath::Deg(60.0),
+            width as f32 / height.max(1) as f32,
+            self.near,
+            self.far,
+        )
+    }
+
+    fn view_projection(&self, width: u32, height: u32) -> Matrix {
+        Matrix {
+            _matrix: (OPENGL_TO_WGPU_MATRIX * self.projection(width, height) * self.view()).into(),
+        }
+    }
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+// The eight corners of a glTF bounding box after `transform`.
+fn box_corners(bounds: &gltf::mesh::BoundingBox, transform: Matrix4<f32>) -> Vec<Point3<f32>> {
+    (0..8)
+        .map(|i| {
+            let corner = Point3::new(
+                if i & 1 == 0 {
+                    bounds.min[0]
+                } else {
+                    bounds.max[0]
+                },
+                if i & 2 == 0 {
+                    bounds.min[1]
+                } else {
+                    bounds.max[1]
+                },
+                if i & 4 == 0 {
+                    bounds.min[2]
+                } else {
+                    bounds.max[2]
+                },
+            );
+            transform.transform_point(corner)
+        })
+        .collect()
+}
+
+// The `index`-th light circles the teapot on its own orbit, height, speed and hue. The
+// fractional parts of multiples of irrational numbers spread the lights out evenly without a
+// random number generator.
+fn animated_light(index: usize, time: f32) -> cluster::PointLight {
+    let i = index as f32;
+    let radius = 0.3 + 2.2 * (i * 0.618_034).fract();
+    let height = 0.02 + 0.6 * (i * 0.754_878).fract();
+    let direction = if index.is_multiple_of(2) { 1.0 } else { -1.0 };
+    let speed = direction * (0.2 + 0.6 * (i * 0.569_840).fract()) / radius.sqrt();
+    let angle = i * 2.4 + time * speed;
+    let hue = (i * 0.38).fract();
+    let color = [
+        (hue * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+        ((hue + 1.0 / 3.0) * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+        ((hue + 2.0 / 3.0) * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+    ];
+    cluster::PointLight::new(
+        [angle.cos() * radius, height, angle.sin() * radius],
+        color,
+        0.4,
+    )
+}
+
+fn cluster_title(light_count: usize, debug: bool) -> String {
+    format!(
+        "sample_11_04: {} point lights (Up/Down){}",
+        light_count,
+        if debug {
+            ", lights per cluster (V)"
+        } else {
+            ""
+        }
+    )
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let teapot = include_bytes!("../assets/teapot.glb");
+    let (document, buffers, _images) = gltf::import_slice(teapot).unwrap();
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/11_04.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    let camera = Camera {
+        eye: Point3::new(2.0, 1.5, 3.0),
+        near: 0.1,
+        far: 20.0,
+    };
+
+    // The teapot is authored in centimeters. It stands on a ground plane that the lights
+    // circle just above.
+    let model =
+        Matrix4::from_translation(cgmath::vec3(0.0, -0.25, 0.0)) * Matrix4::from_scale(0.01);
+    let mut objects = scene
+        .primitives
+        .into_iter()
+        .map(|primitive| {
+            let transform = model * primitive.transform;
+            (primitive, transform)
+        })
+        .collect::<Vec<_>>();
+    let ground = objects
+        .iter()
+        .flat_map(|(primitive, transform)| box_corners(&primitive.bounds, *transform))
+        .map(|p| p.y)
+        .fold(f32::MAX, f32::min);
+    objects.push((
+        loader::create_plane(&device, 3.0),
+        Matrix4::from_translation(cgmath::vec3(0.0, ground, 0.0)),
+    ));
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&camera.view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: objects.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, (_, transform)) in objects.iter().enumerate() {
+        queue.write_buffer(
+            &local_matrix_buffer,
+            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(&Matrix {
+                _matrix: (*transform).into(),
+            }),
+        );
+    }
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let mut light_clusters = cluster::LightClusters::new(&device, MAX_LIGHTS);
+    let mut light_count = 256;
+    window.set_title(&cluster_title(light_count, light_clusters.debug));
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            light_clusters.bind_group_layout(),
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+    let mut depth_texture = texture::Texture::create_depth_texture(&device, &sc_desc, "depth");
+
+    let start = std::time::Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                depth_texture = texture::Texture::create_depth_texture(&device, &sc_desc, "depth");
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+            }
+            // Up and Down change the number of lights, V shows how many lights each cluster
+            // has to shade.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::Up => light_count = (light_count + LIGHT_STEP).min(MAX_LIGHTS),
+                    VirtualKeyCode::Down => light_count = light_count.saturating_sub(LIGHT_STEP),
+                    VirtualKeyCode::V => light_clusters.debug = !light_clusters.debug,
+                    _ => return,
+                }
+                window.set_title(&cluster_title(light_count, light_clusters.debug));
+            }
+            Event::MainEventsCleared => {
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let time = start.elapsed().as_secs_f32();
+                let lights = (0..light_count)
+                    .map(|i| animated_light(i, time))
+                    .collect::<Vec<_>>();
+                light_clusters.update(&queue, &camera, sc_desc.width, sc_desc.height, &lights);
+
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                light_clusters.assign(&mut encoder);
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, light_clusters.bind_group(), &[]);
+                    for (i, (primitive, _)) in objects.iter().enumerate() {
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub bounds: gltf::mesh::BoundingBox,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .map(|(p, n)| Vertex {
+                            _pos: [p[0], p[1], p[2], 1.0],
+                            _normal: n,
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        bounds: primitive.bounding_box(),
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+
+    // A square in the XZ plane facing up, `half_size` from the center to each edge.
+    pub fn create_plane(device: &Device, half_size: f32) -> Primitive {
+        let vertices = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+            .iter()
+            .map(|[x, z]| Vertex {
+                _pos: [x * half_size, 0.0, z * half_size, 1.0],
+                _normal: [0.0, 1.0, 0.0],
+            })
+            .collect::<Vec<_>>();
+        let indices: [u32; 6] = [0, 2, 1, 0, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        Primitive {
+            transform: Matrix4::identity(),
+            bounds: gltf::mesh::BoundingBox {
+                min: [-half_size, 0.0, -half_size],
+                max: [half_size, 0.0, half_size],
+            },
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+// Clustered forward shading. A compute pass splits the view frustum into a grid of clusters
+// and lists the point lights whose range reaches each of them. The lighting pass then only
+// loops over the list of the cluster a fragment falls into.
+mod cluster {
+    use super::Camera;
+    use bytemuck::{Pod, Zeroable};
+    use std::{borrow::Cow, mem::size_of};
+
+    // Screen tiles across and down, and depth slices. Must match the shaders.
+    const CLUSTERS: [u32; 3] = [16, 9, 24];
+    // The light count of a cluster followed by up to 63 light indices.
+    const CLUSTER_STRIDE: u32 = 64;
+    const WORKGROUP_SIZE: u32 = 64;
+
+    // Matches `PointLight` in the shaders, 32 bytes per entry.
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, Pod, Zeroable)]
+    pub struct PointLight {
+        _position: [f32; 3],
+        _range: f32,
+        _color: [f32; 3],
+        _pad: f32,
+    }
+
+    impl PointLight {
+        // The light fades out completely at `range`.
+        pub fn new(position: [f32; 3], color: [f32; 3], range: f32) -> Self {
+            Self {
+                _position: position,
+                _range: range,
+                _color: color,
+                _pad: 0.0,
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, Pod, Zeroable)]
+    struct ClusterUniform {
+        _view: [[f32; 4]; 4],
+        _projection_scale: [f32; 2],
+        _screen_size: [f32; 2],
+        _eye_position: [f32; 3],
+        _near: f32,
+        _far: f32,
+        _light_count: u32,
+        _debug: u32,
+        _pad: u32,
+    }
+
+    pub struct LightClusters {
+        // Shade each cluster by its light count instead of lighting it.
+        pub debug: bool,
+        max_lights: usize,
+        uniform_buffer: wgpu::Buffer,
+        light_buffer: wgpu::Buffer,
+        assign_pipeline: wgpu::ComputePipeline,
+        assign_bind_group: wgpu::BindGroup,
+        bind_group_layout: wgpu::BindGroupLayout,
+        bind_group: wgpu::BindGroup,
+    }
+
+    impl LightClusters {
+        pub fn new(device: &wgpu::Device, max_lights: usize) -> Self {
+            let cluster_count = CLUSTERS[0] * CLUSTERS[1] * CLUSTERS[2];
+
+            let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("clusters"),
+                size: size_of::<ClusterUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("point lights"),
+                size: (max_lights.max(1) * size_of::<PointLight>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let cluster_light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("cluster lights"),
+                size: (cluster_count * CLUSTER_STRIDE) as wgpu::BufferAddress
+                    * size_of::<u32>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::STORAGE,
+                mapped_at_creation: false,
+            });
+
+            // The compute pass writes the cluster lists that the lighting pass only reads.
+            let layout = |visibility, read_only| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                    label: Some("light_clusters_bind_group_layout"),
+                })
+            };
+            let assign_bind_group_layout = layout(wgpu::ShaderStage::COMPUTE, false);
+            let bind_group_layout = layout(wgpu::ShaderStage::FRAGMENT, true);
+
+            let bind_group = |layout| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: light_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: cluster_light_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("light_clusters_bind_group"),
+                })
+            };
+            let assign_bind_group = bind_group(&assign_bind_group_layout);
+            let bind_group = bind_group(&bind_group_layout);
+
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("light_clusters"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/light_clusters.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("light_clusters"),
+                bind_group_layouts: &[&assign_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let assign_pipeline =
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some("light_clusters"),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point: "cs_main",
+                });
+
+            Self {
+                debug: false,
+                max_lights,
+                uniform_buffer,
+                light_buffer,
+                assign_pipeline,
+                assign_bind_group,
+                bind_group_layout,
+                bind_group,
+            }
+        }
+
+        // Uploads the camera and the lights of this frame. Lights beyond the capacity given
+        // to `new` are ignored.
+        pub fn update(
+            &self,
+            queue: &wgpu::Queue,
+            camera: &Camera,
+            width: u32,
+            height: u32,
+            lights: &[PointLight],
+        ) {
+            let lights = &lights[..lights.len().min(self.max_lights)];
+            let projection = camera.projection(width, height);
+            let uniform = ClusterUniform {
+                _view: camera.view().into(),
+                _projection_scale: [projection.x.x, projection.y.y],
+                _screen_size: [width as f32, height as f32],
+                _eye_position: camera.eye.into(),
+                _near: camera.near,
+                _far: camera.far,
+                _light_count: lights.len() as u32,
+                _debug: self.debug as u32,
+                _pad: 0,
+            };
+            queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+            if !lights.is_empty() {
+                queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(lights));
+            }
+        }
+
+        // Rebuilds the light list of every cluster. Must run before the lighting pass.
+        pub fn assign(&self, encoder: &mut wgpu::CommandEncoder) {
+            let cluster_count = CLUSTERS[0] * CLUSTERS[1] * CLUSTERS[2];
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("light_clusters"),
+            });
+            cpass.set_pipeline(&self.assign_pipeline);
+            cpass.set_bind_group(0, &self.assign_bind_group, &[]);
+            cpass.dispatch(cluster_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+            &self.bind_group_layout
+        }
+
+        pub fn bind_group(&self) -> &wgpu::BindGroup {
+            &self.bind_group
+        }
+    }
+}
+
+mod texture {
+    // Only used as the depth attachment, so the view is all that is read. The texture is held
+    // to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        pub fn create_depth_texture(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            label: &str,
+        ) -> Self {
+            let size = wgpu::Extent3d {
+                // 2.
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            };
+            let desc = wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT // 3.
+                | wgpu::TextureUsage::SAMPLED,
+            };
+            let texture = device.create_texture(&desc);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+            Self {
+                _texture: texture,
+                view,
+            }
+        }
+    }
+}
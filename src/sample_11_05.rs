@@ -0,0 +1,1341 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, SquareMatrix, Transform};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[path = "common/light.rs"]
+mod light;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+// The model matrix and the material of one object, see `Local` in the shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Local {
+    _model: [[f32; 4]; 4],
+    _base_color: [f32; 4],
+    _roughness: f32,
+    _metallic: f32,
+    _specular: f32,
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Frame {
+    _inverse_view_projection: [[f32; 4]; 4],
+    _debug_view: u32,
+    _shading: u32,
+    _pad: [u32; 2],
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const EYE_POSITION: [f32; 3] = [0.0, 1.2, 3.0];
+
+fn view_projection(width: u32, height: u32) -> Matrix4<f32> {
+    let projection = cgmath::perspective(
+        cgmath::Deg(60.0),
+        width as f32 / height.max(1) as f32,
+        0.1,
+        100.0,
+    );
+    let view = Matrix4::look_at_rh(
+        Point3::from(EYE_POSITION),
+        Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+    );
+    OPENGL_TO_WGPU_MATRIX * projection * view
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+// The eight corners of a glTF bounding box after `transform`.
+fn box_corners(bounds: &gltf::mesh::BoundingBox, transform: Matrix4<f32>) -> Vec<Point3<f32>> {
+    (0..8)
+        .map(|i| {
+            let corner = Point3::new(
+                if i & 1 == 0 {
+                    bounds.min[0]
+                } else {
+                    bounds.max[0]
+                },
+                if i & 2 == 0 {
+                    bounds.min[1]
+                } else {
+                    bounds.max[1]
+                },
+                if i & 4 == 0 {
+                    bounds.min[2]
+                } else {
+                    bounds.max[2]
+                },
+            );
+            transform.transform_point(corner)
+        })
+        .collect()
+}
+
+// The buffer is replaced when the light list grows, and the bind group with it.
+fn create_light_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    lights: &light::LightSet,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: lights.binding(),
+        }],
+        label: Some("light_set_bind_group"),
+    })
+}
+
+// The `index`-th point light on a ring around the teapots, each with its own hue.
+fn ring_light(index: usize) -> light::Light {
+    let angle = index as f32 * 2.4;
+    let hue = index as f32 * 0.38 % 1.0;
+    let color = [
+        (hue * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+        ((hue + 1.0 / 3.0) * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+        ((hue + 2.0 / 3.0) * std::f32::consts::TAU).cos() * 0.5 + 0.5,
+    ];
+    light::Light::Point {
+        position: [angle.cos() * 1.6, 0.3, angle.sin() * 1.2],
+        color,
+        range: 1.5,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shading {
+    // Lambert diffuse with a Phong highlight scaled by the specular channel.
+    Phong,
+    // Lambert diffuse with a Cook-Torrance highlight driven by roughness and metallic.
+    CookTorrance,
+}
+
+impl Shading {
+    fn next(self) -> Self {
+        match self {
+            Shading::Phong => Shading::CookTorrance,
+            Shading::CookTorrance => Shading::Phong,
+        }
+    }
+}
+
+fn deferred_title(shading: Shading, debug_view: gbuffer::DebugView, light_count: usize) -> String {
+    format!(
+        "sample_11_05: {:?} (S), {:?} (V), {} lights (Up/Down)",
+        shading, debug_view, light_count
+    )
+}
+
+fn frame_uniform(
+    width: u32,
+    height: u32,
+    debug_view: gbuffer::DebugView,
+    shading: Shading,
+) -> Frame {
+    Frame {
+        _inverse_view_projection: view_projection(width, height).invert().unwrap().into(),
+        _debug_view: debug_view as u32,
+        _shading: shading as u32,
+        _pad: [0; 2],
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let texture_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        });
+
+    let normal = include_bytes!("../assets/Tileable_stucco_plaster_wall_texture_NORMAL.jpg");
+    let normal_map = texture::Texture::from_bytes(
+        &device,
+        &queue,
+        normal,
+        "Normal Map",
+        texture::TextureRole::NormalMap,
+    )
+    .unwrap();
+
+    let normal_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&normal_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&normal_map.sampler),
+            },
+        ],
+        label: Some("normal_bind_group"),
+    });
+
+    let teapot = include_bytes!("../assets/teapot.glb");
+    let (document, buffers, _images) = gltf::import_slice(teapot).unwrap();
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/11_05.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+    let lighting_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("deferred lighting"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../assets/lights.wgsl"),
+            include_str!("../assets/deferred_lighting.wgsl")
+        ))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    // Three teapots that differ only in their material, so that each G-buffer channel shows
+    // something. The teapot is authored in centimeters.
+    let materials = [
+        // Red plastic
+        ([0.8, 0.1, 0.1, 1.0], 0.4, 0.0, 0.5),
+        // Gold
+        ([1.0, 0.77, 0.34, 1.0], 0.25, 1.0, 1.0),
+        // Chalk
+        ([0.8, 0.8, 0.8, 1.0], 0.9, 0.0, 0.1),
+    ];
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+    let mut primitives = scene.primitives;
+    // Each object is drawn from `primitives[index]` with its own uniforms.
+    let mut objects = Vec::new();
+    for (i, &(base_color, roughness, metallic, specular)) in materials.iter().enumerate() {
+        let model = Matrix4::from_translation(cgmath::vec3((i as f32 - 1.0) * 1.1, -0.25, 0.0))
+            * Matrix4::from_scale(0.01);
+        for (index, primitive) in primitives.iter().enumerate() {
+            let local = Local {
+                _model: (model * primitive.transform).into(),
+                _base_color: base_color,
+                _roughness: roughness,
+                _metallic: metallic,
+                _specular: specular,
+                _pad: 0.0,
+            };
+            objects.push((index, local));
+        }
+    }
+    let ground = objects
+        .iter()
+        .flat_map(|&(index, local)| box_corners(&primitives[index].bounds, local._model.into()))
+        .map(|p| p.y)
+        .fold(f32::MAX, f32::min);
+    primitives.push(loader::create_plane(&device, 3.0));
+    objects.push((
+        primitives.len() - 1,
+        Local {
+            _model: Matrix4::from_translation(cgmath::vec3(0.0, ground, 0.0)).into(),
+            _base_color: [0.5, 0.5, 0.5, 1.0],
+            _roughness: 0.8,
+            _metallic: 0.0,
+            _specular: 0.2,
+            _pad: 0.0,
+        },
+    ));
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&Matrix {
+            _matrix: view_projection(size.width, size.height).into(),
+        }),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let local_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: objects.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, (_, local)) in objects.iter().enumerate() {
+        queue.write_buffer(
+            &local_buffer,
+            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(local),
+        );
+    }
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Local>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    let frame_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("frame_bind_group_layout"),
+        });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Local>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let mut debug_view = gbuffer::DebugView::Lit;
+    let mut shading = Shading::Phong;
+    let frame_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("frame"),
+        contents: bytemuck::bytes_of(&frame_uniform(size.width, size.height, debug_view, shading)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+    let frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &frame_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: frame_buffer.as_entire_binding(),
+        }],
+        label: Some("frame_bind_group"),
+    });
+
+    // A dim key light, a sky/ground fill and a spot light from above. Up and Down add and
+    // remove colored point lights, which only cost one more loop iteration per pixel.
+    let mut lights = light::LightSet::new(&device, EYE_POSITION, [0.02, 0.02, 0.02]);
+    lights.push(light::Light::Directional {
+        direction: [1.0, -1.0, -1.0],
+        color: [0.3, 0.3, 0.3],
+    });
+    lights.push(light::Light::Hemisphere {
+        sky_color: [0.1, 0.1, 0.2],
+        ground_color: [0.05, 0.03, 0.0],
+        up: [0.0, 1.0, 0.0],
+    });
+    lights.push(light::Light::Spot {
+        position: [0.0, 2.5, 0.0],
+        direction: [0.0, -1.0, 0.0],
+        color: [0.6, 0.55, 0.45],
+        angle: 0.5,
+        range: 4.0,
+    });
+    let fixed_lights = lights.len();
+    for i in 0..6 {
+        lights.push(ring_light(i));
+    }
+    lights.update(&device, &queue);
+    window.set_title(&deferred_title(shading, debug_view, lights.len()));
+
+    let geometry_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("geometry"),
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &texture_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+    let mut g_buffer = gbuffer::GBuffer::new(&device, &sc_desc);
+
+    let light_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[light::LightSet::layout_entry(0)],
+            label: Some("light_set_bind_group_layout"),
+        });
+    let mut light_bind_group = create_light_bind_group(&device, &light_bind_group_layout, &lights);
+
+    let lighting_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("lighting"),
+        bind_group_layouts: &[
+            g_buffer.bind_group_layout(),
+            &frame_bind_group_layout,
+            &light_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4 + 3]>() as wgpu::BufferAddress,
+                shader_location: 2,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4 + 3 + 3]>() as wgpu::BufferAddress,
+                shader_location: 3,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: size_of::<[f32; 4 + 3 + 3 + 3]>() as wgpu::BufferAddress,
+                shader_location: 4,
+            },
+        ],
+    }];
+
+    let geometry_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("geometry"),
+        layout: Some(&geometry_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &gbuffer::GBuffer::targets(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: gbuffer::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let lighting_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("lighting"),
+        layout: Some(&lighting_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &lighting_shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &lighting_shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (
+            &instance,
+            &adapter,
+            &shader,
+            &lighting_shader,
+            &geometry_pipeline_layout,
+            &lighting_pipeline_layout,
+        );
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                g_buffer.resize(&device, &sc_desc);
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&Matrix {
+                        _matrix: view_projection(sc_desc.width, sc_desc.height).into(),
+                    }),
+                );
+                queue.write_buffer(
+                    &frame_buffer,
+                    0,
+                    bytemuck::bytes_of(&frame_uniform(
+                        sc_desc.width,
+                        sc_desc.height,
+                        debug_view,
+                        shading,
+                    )),
+                );
+            }
+            // S switches the specular model, V steps through the G-buffer channels and Up and
+            // Down change the number of point lights.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::S => shading = shading.next(),
+                    VirtualKeyCode::V => debug_view = debug_view.next(),
+                    VirtualKeyCode::Up => {
+                        lights.push(ring_light(lights.len() - fixed_lights));
+                        if lights.update(&device, &queue) {
+                            light_bind_group =
+                                create_light_bind_group(&device, &light_bind_group_layout, &lights);
+                        }
+                    }
+                    VirtualKeyCode::Down if lights.len() > fixed_lights => {
+                        lights.pop();
+                        lights.update(&device, &queue);
+                    }
+                    _ => return,
+                }
+                queue.write_buffer(
+                    &frame_buffer,
+                    0,
+                    bytemuck::bytes_of(&frame_uniform(
+                        sc_desc.width,
+                        sc_desc.height,
+                        debug_view,
+                        shading,
+                    )),
+                );
+                window.set_title(&deferred_title(shading, debug_view, lights.len()));
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("geometry"),
+                        color_attachments: &g_buffer.color_attachments(),
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: g_buffer.depth_view(),
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_pipeline(&geometry_pipeline);
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, &normal_bind_group, &[]);
+                    for (i, &(index, _)) in objects.iter().enumerate() {
+                        let primitive = &primitives[index];
+                        rpass.set_bind_group(
+                            1,
+                            &local_bind_group,
+                            &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                as wgpu::DynamicOffset],
+                        );
+                        rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(
+                            primitive.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                    }
+                }
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("lighting"),
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    rpass.set_pipeline(&lighting_pipeline);
+                    rpass.set_bind_group(0, g_buffer.bind_group(), &[]);
+                    rpass.set_bind_group(1, &frame_bind_group, &[]);
+                    rpass.set_bind_group(2, &light_bind_group, &[]);
+                    rpass.draw(0..4, 0..1);
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+        _tangent: [f32; 3],
+        _bitangent: [f32; 3],
+        _tex_coord: [f32; 2],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub bounds: gltf::mesh::BoundingBox,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let tex_coords = reader
+                        .read_tex_coords(0)
+                        .map(|coords| coords.into_f32().collect::<Vec<_>>());
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .zip(reader.read_tangents().unwrap())
+                        .enumerate()
+                        .map(|(i, ((p, n), t))| {
+                            let normal = Vector3::from(n);
+                            let tangent = Vector3::from([t[0], t[1], t[2]]);
+                            let bitangent = normal.cross(tangent);
+                            Vertex {
+                                _pos: [p[0], p[1], p[2], 1.0],
+                                _normal: n,
+                                _tangent: tangent.into(),
+                                _bitangent: bitangent.into(),
+                                _tex_coord: tex_coords
+                                    .as_ref()
+                                    .map_or([0.0, 0.0], |coords| coords[i]),
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        bounds: primitive.bounding_box(),
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+
+    // A square in the XZ plane facing up, `half_size` from the center to each edge. The
+    // normal map is stretched once over the whole square.
+    pub fn create_plane(device: &Device, half_size: f32) -> Primitive {
+        let vertices = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+            .iter()
+            .map(|[x, z]| Vertex {
+                _pos: [x * half_size, 0.0, z * half_size, 1.0],
+                _normal: [0.0, 1.0, 0.0],
+                _tangent: [1.0, 0.0, 0.0],
+                // normal x tangent, like the glTF meshes above.
+                _bitangent: [0.0, 0.0, -1.0],
+                _tex_coord: [(x + 1.0) / 2.0, (z + 1.0) / 2.0],
+            })
+            .collect::<Vec<_>>();
+        let indices: [u32; 6] = [0, 2, 1, 0, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Plane Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        Primitive {
+            transform: Matrix4::identity(),
+            bounds: gltf::mesh::BoundingBox {
+                min: [-half_size, 0.0, -half_size],
+                max: [half_size, 0.0, half_size],
+            },
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+}
+
+// The render targets of deferred shading. The geometry pass writes them all at once and the
+// lighting pass reads them back texel by texel.
+mod gbuffer {
+    use super::texture::RenderTarget;
+
+    pub const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    // World space normals need the sign and more precision than 8 bits give.
+    pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    // Roughness, metallic and the Phong specular intensity.
+    pub const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    // The world position is reconstructed from depth instead of being stored.
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // What the lighting pass shows. Must match `VIEW_*` in the shader.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DebugView {
+        Lit,
+        Albedo,
+        Normal,
+        Roughness,
+        Metallic,
+        Specular,
+        Depth,
+    }
+
+    impl DebugView {
+        pub fn next(self) -> Self {
+            match self {
+                DebugView::Lit => DebugView::Albedo,
+                DebugView::Albedo => DebugView::Normal,
+                DebugView::Normal => DebugView::Roughness,
+                DebugView::Roughness => DebugView::Metallic,
+                DebugView::Metallic => DebugView::Specular,
+                DebugView::Specular => DebugView::Depth,
+                DebugView::Depth => DebugView::Lit,
+            }
+        }
+    }
+
+    pub struct GBuffer {
+        albedo: RenderTarget,
+        normal: RenderTarget,
+        material: RenderTarget,
+        depth: RenderTarget,
+        bind_group_layout: wgpu::BindGroupLayout,
+        bind_group: wgpu::BindGroup,
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        targets: [&RenderTarget; 4],
+    ) -> wgpu::BindGroup {
+        let entry = |binding: u32| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&targets[binding as usize].view),
+        };
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[entry(0), entry(1), entry(2), entry(3)],
+            label: Some("gbuffer_bind_group"),
+        })
+    }
+
+    impl GBuffer {
+        pub fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) -> Self {
+            let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type,
+                },
+                count: None,
+            };
+            // Only read with `textureLoad`, so no sampler and no filtering.
+            let color = wgpu::TextureSampleType::Float { filterable: false };
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[
+                        texture_entry(0, color),
+                        texture_entry(1, color),
+                        texture_entry(2, color),
+                        texture_entry(3, wgpu::TextureSampleType::Depth),
+                    ],
+                    label: Some("gbuffer_bind_group_layout"),
+                });
+
+            let target = |format, label| RenderTarget::new(device, format, label, sc_desc);
+            let albedo = target(ALBEDO_FORMAT, "albedo");
+            let normal = target(NORMAL_FORMAT, "normal");
+            let material = target(MATERIAL_FORMAT, "material");
+            let depth = target(DEPTH_FORMAT, "depth");
+            let bind_group = create_bind_group(
+                device,
+                &bind_group_layout,
+                [&albedo, &normal, &material, &depth],
+            );
+            Self {
+                albedo,
+                normal,
+                material,
+                depth,
+                bind_group_layout,
+                bind_group,
+            }
+        }
+
+        // The targets have to follow the swap chain size. This also replaces the bind group
+        // when they were recreated.
+        pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+            let mut resized = self.albedo.resize(device, sc_desc);
+            resized |= self.normal.resize(device, sc_desc);
+            resized |= self.material.resize(device, sc_desc);
+            resized |= self.depth.resize(device, sc_desc);
+            if resized {
+                self.bind_group = create_bind_group(
+                    device,
+                    &self.bind_group_layout,
+                    [&self.albedo, &self.normal, &self.material, &self.depth],
+                );
+            }
+        }
+
+        // The color targets of the geometry pipeline, in the order of `GBufferOutput`.
+        pub fn targets() -> [wgpu::ColorTargetState; 3] {
+            [
+                ALBEDO_FORMAT.into(),
+                NORMAL_FORMAT.into(),
+                MATERIAL_FORMAT.into(),
+            ]
+        }
+
+        pub fn color_attachments(&self) -> [wgpu::RenderPassColorAttachment<'_>; 3] {
+            let attachment = |view| wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            };
+            [
+                attachment(&self.albedo.view),
+                attachment(&self.normal.view),
+                attachment(&self.material.view),
+            ]
+        }
+
+        pub fn depth_view(&self) -> &wgpu::TextureView {
+            &self.depth.view
+        }
+
+        pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+            &self.bind_group_layout
+        }
+
+        pub fn bind_group(&self) -> &wgpu::BindGroup {
+            &self.bind_group
+        }
+    }
+}
+
+mod texture {
+    use std::{
+        borrow::Cow,
+        num::{NonZeroU32, NonZeroU8},
+    };
+
+    use anyhow::*;
+    use image::GenericImageView;
+
+    // The texture is only held to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        pub sampler: wgpu::Sampler,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TextureRole {
+        // Tangent space normal maps. Stored linearly and unpacked with `* 2.0 - 1.0` in the shader.
+        NormalMap,
+    }
+
+    impl TextureRole {
+        pub fn format(self) -> wgpu::TextureFormat {
+            match self {
+                TextureRole::NormalMap => wgpu::TextureFormat::Rgba8Unorm,
+            }
+        }
+    }
+
+    // A render target with the size of the window, as in sample_10_06. The G-buffer only reads
+    // it with `textureLoad`, so there is no sampler.
+    pub struct RenderTarget {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        label: &'static str,
+        extent: wgpu::Extent3d,
+    }
+
+    impl RenderTarget {
+        pub fn new(
+            device: &wgpu::Device,
+            format: wgpu::TextureFormat,
+            label: &'static str,
+            sc_desc: &wgpu::SwapChainDescriptor,
+        ) -> Self {
+            let extent = Self::extent(sc_desc);
+            let (texture, view) = Self::create(device, format, label, extent);
+            Self {
+                _texture: texture,
+                view,
+                format,
+                label,
+                extent,
+            }
+        }
+
+        // Call when the swap chain is recreated. Returns whether the texture was replaced, in
+        // which case bind groups using it have to be recreated as well.
+        pub fn resize(
+            &mut self,
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+        ) -> bool {
+            let extent = Self::extent(sc_desc);
+            if extent == self.extent {
+                return false;
+            }
+            let (texture, view) = Self::create(device, self.format, self.label, extent);
+            self._texture = texture;
+            self.view = view;
+            self.extent = extent;
+            true
+        }
+
+        fn extent(sc_desc: &wgpu::SwapChainDescriptor) -> wgpu::Extent3d {
+            wgpu::Extent3d {
+                width: sc_desc.width.max(1),
+                height: sc_desc.height.max(1),
+                depth_or_array_layers: 1,
+            }
+        }
+
+        fn create(
+            device: &wgpu::Device,
+            format: wgpu::TextureFormat,
+            label: &str,
+            size: wgpu::Extent3d,
+        ) -> (wgpu::Texture, wgpu::TextureView) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        }
+    }
+
+    impl Texture {
+        pub fn from_bytes(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            bytes: &[u8],
+            label: &str,
+            role: TextureRole,
+        ) -> Result<Self> {
+            let img = image::load_from_memory(bytes)?;
+            Self::from_image(device, queue, &img, Some(label), role)
+        }
+
+        pub fn from_image(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            img: &image::DynamicImage,
+            label: Option<&str>,
+            role: TextureRole,
+        ) -> Result<Self> {
+            let rgba = img.to_rgba8().to_vec();
+            let dimensions = img.dimensions();
+
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let format = role.format();
+            let mip_level_count = Self::mip_level_count(dimensions.0, dimensions.1);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::RENDER_ATTACHMENT,
+            });
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(4 * dimensions.0),
+                    rows_per_image: NonZeroU32::new(dimensions.1),
+                },
+                size,
+            );
+
+            Self::generate_mipmaps(device, queue, &texture, format, mip_level_count);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            // Trilinear filtering with anisotropy so that minified textures don't shimmer.
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: f32::MAX,
+                anisotropy_clamp: NonZeroU8::new(16),
+                ..Default::default()
+            });
+
+            Ok(Self {
+                _texture: texture,
+                view,
+                sampler,
+            })
+        }
+
+        fn mip_level_count(width: u32, height: u32) -> u32 {
+            32 - width.max(height).leading_zeros()
+        }
+
+        // Fill mip levels 1.. by repeatedly rendering the previous level into the next one.
+        // Going through a render pass (instead of a compute shader) lets sRGB textures be
+        // filtered in linear space: the sampler decodes and the render target encodes again.
+        fn generate_mipmaps(
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            texture: &wgpu::Texture,
+            format: wgpu::TextureFormat,
+            mip_level_count: u32,
+        ) {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../assets/mipmap.wgsl"
+                ))),
+                flags: wgpu::ShaderFlags::all(),
+            });
+
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("mipmap"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[format.into()],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+            });
+
+            let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mipmap"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let views = (0..mip_level_count)
+                .map(|mip| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("mip"),
+                        format: None,
+                        dimension: None,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: 0,
+                        array_layer_count: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            for target_mip in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: &views[target_mip],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..4, 0..1);
+            }
+
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
@@ -0,0 +1,781 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Matrix4, Point3};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+use winit::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Matrix {
+    _matrix: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct ToonUniform {
+    _eye_position: [f32; 3],
+    _ramp: f32,
+    _light_direction: [f32; 3],
+    _specular_threshold: f32,
+    _base_color: [f32; 3],
+    _shininess: f32,
+    _outline_color: [f32; 3],
+    _outline_width: f32,
+    _viewport_size: [f32; 2],
+    _pad: [f32; 2],
+}
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// An orbit camera around the teapot. Yaw and pitch are in degrees.
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Camera {
+    const DISTANCE: f32 = 3.0;
+
+    fn eye(&self) -> Point3<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Point3::new(
+            Self::DISTANCE * pitch.cos() * yaw.sin(),
+            Self::DISTANCE * pitch.sin(),
+            Self::DISTANCE * pitch.cos() * yaw.cos(),
+        )
+    }
+
+    fn view_projection(&self, width: u32, height: u32) -> Matrix {
+        let projection = cgmath::perspective(
+            cgmath::Deg(60.0),
+            width as f32 / height.max(1) as f32,
+            0.1,
+            100.0,
+        );
+        let view = Matrix4::look_at_rh(
+            self.eye(),
+            Point3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_y(),
+        );
+        Matrix {
+            _matrix: (OPENGL_TO_WGPU_MATRIX * projection * view).into(),
+        }
+    }
+}
+
+// A lighting ramp: each band starts at a Lambert term and holds one sRGB color up to the
+// start of the next band.
+struct Ramp {
+    name: &'static str,
+    bands: &'static [(f32, [u8; 3])],
+}
+
+const RAMPS: [Ramp; 4] = [
+    Ramp {
+        name: "two tone",
+        bands: &[(0.0, [110, 110, 110]), (0.4, [255, 255, 255])],
+    },
+    Ramp {
+        name: "three tone",
+        bands: &[
+            (0.0, [90, 90, 90]),
+            (0.25, [180, 180, 180]),
+            (0.7, [255, 255, 255]),
+        ],
+    },
+    Ramp {
+        name: "four tone",
+        bands: &[
+            (0.0, [70, 70, 70]),
+            (0.2, [140, 140, 140]),
+            (0.5, [205, 205, 205]),
+            (0.8, [255, 255, 255]),
+        ],
+    },
+    // Cool shadows and warm light, like a lot of anime shading.
+    Ramp {
+        name: "warm and cool",
+        bands: &[
+            (0.0, [95, 85, 150]),
+            (0.3, [200, 170, 180]),
+            (0.75, [255, 245, 230]),
+        ],
+    },
+];
+
+const RAMP_WIDTH: u32 = 256;
+
+// Every ramp as one row of an RGBA8 image.
+fn ramp_texels() -> Vec<u8> {
+    RAMPS
+        .iter()
+        .flat_map(|ramp| {
+            (0..RAMP_WIDTH).flat_map(move |x| {
+                let lambert = (x as f32 + 0.5) / RAMP_WIDTH as f32;
+                let (_, [r, g, b]) = ramp
+                    .bands
+                    .iter()
+                    .rev()
+                    .find(|(start, _)| lambert >= *start)
+                    .unwrap_or(&ramp.bands[0]);
+                vec![*r, *g, *b, 255]
+            })
+        })
+        .collect()
+}
+
+fn toon_uniform(
+    camera: &Camera,
+    ramp: usize,
+    outline_width: f32,
+    sc_desc: &wgpu::SwapChainDescriptor,
+) -> ToonUniform {
+    ToonUniform {
+        _eye_position: camera.eye().into(),
+        _ramp: (ramp as f32 + 0.5) / RAMPS.len() as f32,
+        _light_direction: cgmath::vec3(1.0f32, -1.0, -1.0).normalize().into(),
+        _specular_threshold: 0.5,
+        _base_color: [1.0, 0.45, 0.3],
+        _shininess: 40.0,
+        _outline_color: [0.05, 0.03, 0.03],
+        _outline_width: outline_width,
+        _viewport_size: [sc_desc.width as f32, sc_desc.height as f32],
+        _pad: [0.0; 2],
+    }
+}
+
+fn toon_title(ramp: usize, outline_width: f32) -> String {
+    format!(
+        "sample_11_06: {} ramp (R), outline {:.1} px (Page Up/Down)",
+        RAMPS[ramp].name, outline_width
+    )
+}
+
+// Shaders output linear colors, so always present through an sRGB swap chain and let the
+// hardware encode them. Otherwise the same shader looks darker or brighter per platform.
+fn srgb_swapchain_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window) {
+    let size = window.inner_size();
+    let instance = wgpu::Instance::new(wgpu::BackendBit::all());
+    let surface = unsafe { instance.create_surface(&window) };
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            // Request an adapter which can render to our surface
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    // Create the logical device and command queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+    let teapot = include_bytes!("../assets/teapot.glb");
+    let (document, buffers, _images) = gltf::import_slice(teapot).unwrap();
+    let scene = loader::load_first_scene(&device, &document, &buffers);
+
+    // Load the shaders from disk
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../assets/11_06.wgsl"))),
+        flags: wgpu::ShaderFlags::all(),
+    });
+
+    let swapchain_format =
+        srgb_swapchain_format(adapter.get_swap_chain_preferred_format(&surface).unwrap());
+
+    let mut sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: swapchain_format,
+        width: size.width,
+        height: size.height,
+        present_mode: wgpu::PresentMode::Mailbox,
+    };
+
+    let mut camera = Camera {
+        yaw: 30.0,
+        pitch: 15.0,
+    };
+    let mut current_ramp = 1;
+    let mut outline_width = 3.0f32;
+    window.set_title(&toon_title(current_ramp, outline_width));
+
+    let view_projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("view projection matrix"),
+        contents: bytemuck::bytes_of(&camera.view_projection(size.width, size.height)),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    // The teapot is authored in centimeters.
+    let model =
+        Matrix4::from_translation(cgmath::vec3(0.0, -0.25, 0.0)) * Matrix4::from_scale(0.01);
+    let local_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: scene.primitives.len() as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    for (i, primitive) in scene.primitives.iter().enumerate() {
+        queue.write_buffer(
+            &local_matrix_buffer,
+            i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT,
+            bytemuck::bytes_of(&Matrix {
+                _matrix: (model * primitive.transform).into(),
+            }),
+        );
+    }
+
+    let toon_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("toon"),
+        contents: bytemuck::bytes_of(&toon_uniform(
+            &camera,
+            current_ramp,
+            outline_width,
+            &sc_desc,
+        )),
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let ramp_texture = device.create_texture_with_data(
+        &queue,
+        &wgpu::TextureDescriptor {
+            label: Some("ramps"),
+            size: wgpu::Extent3d {
+                width: RAMP_WIDTH,
+                height: RAMPS.len() as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        },
+        &ramp_texels(),
+    );
+    let ramp_view = ramp_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Nearest keeps the bands hard and never blends two ramps together.
+    let ramp_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("ramps"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    let uniform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+    let local_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        size_of::<Matrix>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+    // The outline width is needed by the vertex stage, the rest by the fragment stage.
+    let toon_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<ToonUniform>() as wgpu::BufferAddress
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("toon_bind_group_layout"),
+        });
+
+    let view_projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &uniform_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: view_projection_buffer.as_entire_binding(),
+        }],
+        label: Some("view_projection_bind_group"),
+    });
+
+    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &local_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &local_matrix_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size_of::<Matrix>() as wgpu::BufferAddress),
+            }),
+        }],
+        label: None,
+    });
+
+    let toon_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &toon_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: toon_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&ramp_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&ramp_sampler),
+            },
+        ],
+        label: Some("toon_bind_group"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[
+            &uniform_bind_group_layout,
+            &local_bind_group_layout,
+            &toon_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_size = std::mem::size_of::<loader::Vertex>();
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: vertex_size as wgpu::BufferAddress,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                shader_location: 1,
+            },
+        ],
+    }];
+
+    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    // Only the back faces are drawn, so the extruded hull hides behind the teapot everywhere
+    // except around its silhouette.
+    let outline_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("outline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_outline",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_outline",
+            targets: &[swapchain_format.into()],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Front),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+    });
+
+    let mut depth_texture =
+        texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+
+    let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+    event_loop.run(move |event, _, control_flow| {
+        // Have the closure take ownership of the resources.
+        // `event_loop.run` never returns, therefore we must do this to ensure
+        // the resources are properly cleaned up.
+        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                // Recreate the swap chain with the new size
+                sc_desc.width = size.width.max(1);
+                sc_desc.height = size.height.max(1);
+                swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                depth_texture =
+                    texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                // The outline width is in pixels, so it depends on the viewport size.
+                queue.write_buffer(
+                    &toon_buffer,
+                    0,
+                    bytemuck::bytes_of(&toon_uniform(
+                        &camera,
+                        current_ramp,
+                        outline_width,
+                        &sc_desc,
+                    )),
+                );
+            }
+            // Arrow keys orbit the camera, R steps through the ramps and Page Up/Down change
+            // the outline width.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                match keycode {
+                    VirtualKeyCode::Left => camera.yaw -= 10.0,
+                    VirtualKeyCode::Right => camera.yaw += 10.0,
+                    VirtualKeyCode::Up => camera.pitch = (camera.pitch + 10.0).min(80.0),
+                    VirtualKeyCode::Down => camera.pitch = (camera.pitch - 10.0).max(-80.0),
+                    VirtualKeyCode::R => current_ramp = (current_ramp + 1) % RAMPS.len(),
+                    VirtualKeyCode::PageUp => outline_width = (outline_width + 0.5).min(10.0),
+                    VirtualKeyCode::PageDown => outline_width = (outline_width - 0.5).max(0.0),
+                    _ => return,
+                }
+                window.set_title(&toon_title(current_ramp, outline_width));
+                queue.write_buffer(
+                    &view_projection_buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.view_projection(sc_desc.width, sc_desc.height)),
+                );
+                queue.write_buffer(
+                    &toon_buffer,
+                    0,
+                    bytemuck::bytes_of(&toon_uniform(
+                        &camera,
+                        current_ramp,
+                        outline_width,
+                        &sc_desc,
+                    )),
+                );
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let frame = swap_chain
+                    .get_current_frame()
+                    .expect("Failed to acquire next swap chain texture")
+                    .output;
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachment {
+                            view: &frame.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color {
+                                    r: 0.6,
+                                    g: 0.75,
+                                    b: 0.9,
+                                    a: 1.0,
+                                }),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    rpass.set_bind_group(0, &view_projection_bind_group, &[]);
+                    rpass.set_bind_group(2, &toon_bind_group, &[]);
+                    for &pipeline in &[&render_pipeline, &outline_pipeline] {
+                        rpass.set_pipeline(pipeline);
+                        for (i, primitive) in scene.primitives.iter().enumerate() {
+                            rpass.set_bind_group(
+                                1,
+                                &local_bind_group,
+                                &[(i as wgpu::BufferAddress * wgpu::BIND_BUFFER_ALIGNMENT)
+                                    as wgpu::DynamicOffset],
+                            );
+                            rpass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                            rpass.set_index_buffer(
+                                primitive.index_buffer.slice(..),
+                                wgpu::IndexFormat::Uint32,
+                            );
+                            rpass.draw_indexed(0..primitive.index_count, 0, 0..1);
+                        }
+                    }
+                }
+
+                queue.submit(Some(encoder.finish()));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let window = winit::window::Window::new(&event_loop).unwrap();
+    env_logger::init();
+    // Temporarily avoid srgb formats for the swapchain on the web
+    pollster::block_on(run(event_loop, window));
+}
+
+mod loader {
+    use super::*;
+    use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+    use wgpu::Device;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct Vertex {
+        _pos: [f32; 4],
+        _normal: [f32; 3],
+    }
+
+    pub struct Primitive {
+        pub transform: Matrix4<f32>,
+        pub vertex_buffer: wgpu::Buffer,
+        pub index_buffer: wgpu::Buffer,
+        pub index_count: u32,
+    }
+
+    pub struct Scene {
+        pub primitives: Vec<Primitive>,
+    }
+
+    pub fn load_first_scene(
+        device: &Device,
+        root: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+    ) -> Scene {
+        let scene = root.scenes().next().unwrap();
+        let mut primitives = Vec::new();
+
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Matrix4::<f32>::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((node, transform)) = nodes.pop() {
+            let (trans, rot, scale) = node.transform().decomposed();
+
+            let transform = transform
+                * Matrix4::from_translation(Vector3::from(trans))
+                * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+                * Matrix4::from(Quaternion::new(rot[3], rot[0], rot[1], rot[2]));
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let vertices = reader
+                        .read_positions()
+                        .unwrap()
+                        .zip(reader.read_normals().unwrap())
+                        .map(|(p, n)| Vertex {
+                            _pos: [p[0], p[1], p[2], 1.0],
+                            _normal: n,
+                        })
+                        .collect::<Vec<_>>();
+                    let indices = reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .collect::<Vec<_>>();
+
+                    let vertex_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Vertex Buffer"),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsage::VERTEX,
+                        });
+
+                    let index_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Index Buffer"),
+                            contents: bytemuck::cast_slice(&indices),
+                            usage: wgpu::BufferUsage::INDEX,
+                        });
+
+                    primitives.push(Primitive {
+                        transform,
+                        vertex_buffer,
+                        index_buffer,
+                        index_count: indices.len() as u32,
+                    })
+                }
+            }
+
+            nodes.extend(node.children().map(|node| (node, transform)));
+        }
+
+        Scene { primitives }
+    }
+}
+
+mod texture {
+    // Only used as the depth attachment, so the view is all that is read. The texture is held
+    // to keep it alive as long as the view.
+    pub struct Texture {
+        _texture: wgpu::Texture,
+        pub view: wgpu::TextureView,
+    }
+
+    impl Texture {
+        pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+        pub fn create_depth_texture(
+            device: &wgpu::Device,
+            sc_desc: &wgpu::SwapChainDescriptor,
+            label: &str,
+        ) -> Self {
+            let size = wgpu::Extent3d {
+                // 2.
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth_or_array_layers: 1,
+            };
+            let desc = wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT // 3.
+                | wgpu::TextureUsage::SAMPLED,
+            };
+            let texture = device.create_texture(&desc);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+            Self {
+                _texture: texture,
+                view,
+            }
+        }
+    }
+}